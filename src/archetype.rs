@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::flocking::SteeringWeights;
+
+#[derive(Clone, Copy, Component, Debug, Deserialize, Eq, Hash, PartialEq)]
+pub enum Archetype {
+    Common,
+    /// Fast but weak, vanishes quickly in the vacuum
+    Wisp,
+    /// Slow and hits hard, takes a long time to vacuum
+    Tank,
+    /// Splits into two wisps when vacuumed
+    Splitter,
+    /// Phases out from time to time and can't be vacuumed while it is
+    Phantom,
+}
+
+/// Multipliers applied on top of the values of the current wave
+pub struct ArchetypeStats {
+    pub speed: f32,
+    pub damage: f32,
+    pub suck_resistance: f32,
+    /// Heavier ghosts are pulled towards the nozzle more slowly
+    pub mass: f32,
+    pub scale: f32,
+    /// Solid ghosts find their way around walls, the others float right through them
+    pub solid: bool,
+    pub steering: SteeringWeights,
+    /// All archetypes share the ghost model, this is multiplied onto its color and glow, so they can be told apart
+    pub tint: Color,
+}
+
+impl Archetype {
+    pub fn stats(&self) -> ArchetypeStats {
+        match self {
+            Archetype::Common => ArchetypeStats {
+                speed: 1.0,
+                damage: 1.0,
+                suck_resistance: 1.0,
+                mass: 1.0,
+                scale: 1.0,
+                solid: false,
+                steering: SteeringWeights {
+                    separation: 1.5,
+                    alignment: 0.5,
+                    seek: 1.0,
+                },
+                tint: Color::WHITE,
+            },
+            Archetype::Wisp => ArchetypeStats {
+                speed: 1.8,
+                damage: 0.5,
+                suck_resistance: 0.5,
+                mass: 0.5,
+                scale: 0.6,
+                solid: false,
+                steering: SteeringWeights {
+                    separation: 0.8,
+                    alignment: 1.2,
+                    seek: 1.0,
+                },
+                tint: Color::rgb(0.55, 0.95, 1.0),
+            },
+            Archetype::Tank => ArchetypeStats {
+                speed: 0.6,
+                damage: 1.5,
+                suck_resistance: 2.5,
+                mass: 3.0,
+                scale: 1.5,
+                solid: true,
+                steering: SteeringWeights {
+                    separation: 2.5,
+                    alignment: 0.2,
+                    seek: 1.0,
+                },
+                tint: Color::rgb(1.0, 0.4, 0.3),
+            },
+            Archetype::Splitter => ArchetypeStats {
+                speed: 1.0,
+                damage: 1.0,
+                suck_resistance: 1.2,
+                mass: 1.5,
+                scale: 1.2,
+                solid: true,
+                steering: SteeringWeights {
+                    separation: 1.5,
+                    alignment: 0.5,
+                    seek: 1.0,
+                },
+                tint: Color::rgb(0.5, 1.0, 0.4),
+            },
+            Archetype::Phantom => ArchetypeStats {
+                speed: 1.1,
+                damage: 1.0,
+                suck_resistance: 1.0,
+                mass: 1.0,
+                scale: 1.0,
+                solid: false,
+                steering: SteeringWeights {
+                    separation: 1.0,
+                    alignment: 0.3,
+                    seek: 1.0,
+                },
+                tint: Color::rgb(0.75, 0.45, 1.0),
+            },
+        }
+    }
+}
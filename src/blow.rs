@@ -0,0 +1,147 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{boss::Boss, component::*, events::VacuumEvent, resource::Stats, GameSet, GameState, playing};
+
+/// Shoots captured ghosts out of the nozzle on right click, they knock back ghosts and hurt bosses they hit
+pub struct BlowPlugin;
+
+impl Plugin for BlowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Game), remove_all_blown_ghosts)
+            .add_systems(Update, (
+                blow,
+                expire_blown_ghosts,
+                detect_hits,
+                knock_back,
+            ).chain().in_set(GameSet::Blow).run_if(playing));
+    }
+}
+
+const BLOW_SPEED: f32 = 12.0;
+const BLOWN_LIFETIME: f32 = 2.0;
+const KNOCKBACK_SPEED: f32 = 6.0;
+const KNOCKBACK_TIME: f32 = 0.4;
+/// Health a boss loses per blown ghost, the same as vacuuming it for as long as a regular ghost takes
+const BOSS_DAMAGE: f32 = 1.0;
+
+/// A captured ghost that has been blown out of the nozzle
+#[derive(Component)]
+struct BlownGhost(Timer);
+
+fn remove_all_blown_ghosts(
+    query: Query<Entity, With<BlownGhost>>,
+    mut commands: Commands,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn blow(
+    asset_server: Res<AssetServer>,
+    mut stats: ResMut<Stats>,
+    mut vacuum_events: EventReader<VacuumEvent>,
+    nozzles: Query<&GlobalTransform, With<Nozzle>>,
+    mut commands: Commands,
+) {
+    let Ok(nozzle) = nozzles.get_single() else {
+        return;
+    };
+    for vacuum_event in vacuum_events.read() {
+        if !matches!(vacuum_event, VacuumEvent::Blow) || stats.tank == 0 {
+            continue;
+        }
+        stats.tank -= 1;
+
+        // we have to use down here as forward because the nozzle is rotated by 90°
+        let mut direction = nozzle.down();
+        direction.y = 0.0;
+        let direction = direction.normalize_or_zero();
+        let position = nozzle.translation() + direction;
+
+        let id = commands.spawn(SceneBundle {
+            scene: asset_server.load("ghost.glb#Scene0"),
+            transform: Transform::from_translation(position)
+                .with_scale(Vec3::ONE * 0.5)
+                .looking_at(position + direction, Vec3::Y),
+            ..default()
+        })
+        .insert(Name::from("BlownGhost"))
+        .insert(BlownGhost(Timer::from_seconds(BLOWN_LIFETIME, TimerMode::Once)))
+        .insert(RigidBody::Dynamic)
+        .insert(Collider::ball(0.3))
+        .insert(GravityScale(0.0))
+        .insert(LockedAxes::TRANSLATION_LOCKED_Y)
+        .insert(Restitution::coefficient(0.8))
+        .insert(Velocity::linear(direction * BLOW_SPEED))
+        .insert(CollisionGroups::new(Group::GROUP_6, Group::GROUP_2 | Group::GROUP_4))
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .id();
+        info!("blew out {:?}", id);
+    }
+}
+
+fn expire_blown_ghosts(
+    time: Res<Time>,
+    mut query: Query<(&mut BlownGhost, Entity)>,
+    mut commands: Commands,
+) {
+    for (mut blown_ghost, entity) in &mut query {
+        blown_ghost.0.tick(time.delta());
+        if blown_ghost.0.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Blown ghosts vanish on the first ghost they hit, which is pushed away along their path
+fn detect_hits(
+    mut collision_events: EventReader<CollisionEvent>,
+    blown_ghosts: Query<&Velocity, With<BlownGhost>>,
+    ghosts: Query<(), With<Ghost>>,
+    mut bosses: Query<&mut Boss>,
+    mut commands: Commands,
+) {
+    for collision_event in collision_events.read() {
+        let CollisionEvent::Started(left, right, _) = collision_event else {
+            continue;
+        };
+        let (blown, target) = if blown_ghosts.contains(*left) { (*left, *right) } else { (*right, *left) };
+        let Ok(velocity) = blown_ghosts.get(blown) else {
+            continue;
+        };
+
+        if let Ok(mut boss) = bosses.get_mut(target) {
+            info!("{:?} hit boss {:?}", blown, target);
+            boss.damage(BOSS_DAMAGE);
+        }
+        else if ghosts.contains(target) {
+            info!("{:?} knocked back {:?}", blown, target);
+            let direction = Vec3::new(velocity.linvel.x, 0.0, velocity.linvel.z).normalize_or_zero();
+            commands.entity(target).try_insert(Knockback {
+                timer: Timer::from_seconds(KNOCKBACK_TIME, TimerMode::Once),
+                direction,
+            });
+        }
+        else {
+            // walls just bounce it off
+            continue;
+        }
+        commands.entity(blown).despawn_recursive();
+    }
+}
+
+fn knock_back(
+    time: Res<Time>,
+    mut query: Query<(&mut Transform, &mut Knockback, Entity)>,
+    mut commands: Commands,
+) {
+    for (mut transform, mut knockback, entity) in &mut query {
+        knockback.timer.tick(time.delta());
+        transform.translation += knockback.direction * KNOCKBACK_SPEED * knockback.timer.percent_left() * time.delta_seconds();
+        if knockback.timer.finished() {
+            commands.entity(entity).remove::<Knockback>();
+        }
+    }
+}
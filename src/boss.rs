@@ -0,0 +1,327 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::resource::GlobalEntropy;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::{
+    archetype::Archetype,
+    collision_events::{CollideWithPlayer, SuckEvent},
+    common::Remap,
+    component::*,
+    enemy_spawner::{GhostSpawnConfig, Spawning, spawn_ghost},
+    events::DamageEvent,
+    resource::{CameraSettings, Stats},
+    waves::BossDefinition,
+    GameSet, GameState, playing,
+};
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Game), kill_all_bosses)
+            // minions vacuumed or crashed into the player this frame are despawned before a defeated boss takes the rest along,
+            // so they aren't eliminated twice
+            .add_systems(Update, apply_deferred.after(GameSet::Enemies).before(GameSet::Boss))
+            .add_systems(Update, (
+                detect_suckage,
+                drain_health,
+                move_boss,
+                attack,
+                move_projectiles,
+                detect_hits,
+            ).chain().in_set(GameSet::Boss).run_if(playing));
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BossAttack {
+    Charge,
+    Summon,
+    Projectile,
+}
+
+const ATTACKS: [BossAttack; 3] = [BossAttack::Charge, BossAttack::Summon, BossAttack::Projectile];
+
+/// A large ghost with a health pool that drains while it is inside the vacuum range
+#[derive(Component)]
+pub struct Boss {
+    definition: BossDefinition,
+    health: f32,
+    in_range: bool,
+    attack_timer: Timer,
+}
+
+impl Boss {
+    fn new(definition: BossDefinition) -> Self {
+        Self {
+            health: definition.health,
+            in_range: false,
+            attack_timer: Timer::from_seconds(definition.attack_interval, TimerMode::Repeating),
+            definition,
+        }
+    }
+
+    pub fn damage(&mut self, amount: f32) {
+        self.health -= amount;
+    }
+
+    pub fn normalized_health(&self) -> f32 {
+        self.health / self.definition.health
+    }
+}
+
+#[derive(Component)]
+struct Charging {
+    timer: Timer,
+    direction: Vec3,
+}
+
+#[derive(Component)]
+struct Projectile {
+    timer: Timer,
+    velocity: Vec3,
+    damage: f32,
+}
+
+/// Minions are despawned together with the boss that summoned them
+#[derive(Component)]
+struct Minion;
+
+pub fn spawn_boss(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    definition: &BossDefinition,
+    position: Vec3,
+) -> Entity {
+    commands.spawn(SceneBundle {
+        scene: asset_server.load("ghost.glb#Scene0"),
+        transform: Transform::from_translation(position).with_scale(Vec3::ONE * definition.scale),
+        ..default()
+    })
+    .insert(Name::from("Boss"))
+    .insert(Boss::new(definition.clone()))
+    .insert(Collider::capsule(Vec3::Y / -4.0, Vec3::Y / 4.0, 0.25))
+    .insert(Sensor)
+    .insert(CollisionGroups::new(Group::GROUP_2, Group::GROUP_1 | Group::GROUP_3 | Group::GROUP_6))
+    .insert(ActiveEvents::COLLISION_EVENTS)
+    .insert(FloatTimer::new((0.5, 1.5)))
+    .insert(Suckable)
+    .insert(Spawning(Timer::new(Duration::from_secs_f32(2.0), TimerMode::Once)))
+    .id()
+}
+
+#[allow(clippy::type_complexity)]
+fn kill_all_bosses(
+    query: Query<Entity, Or<(With<Boss>, With<Projectile>)>>,
+    mut commands: Commands,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn detect_suckage(
+    mut suck_events: EventReader<SuckEvent>,
+    mut bosses: Query<&mut Boss>,
+) {
+    for suck_event in suck_events.read() {
+        match suck_event {
+            SuckEvent::Start(entity) => {
+                if let Ok(mut boss) = bosses.get_mut(*entity) {
+                    boss.in_range = true;
+                }
+            }
+            SuckEvent::Stop(entity) => {
+                if let Ok(mut boss) = bosses.get_mut(*entity) {
+                    boss.in_range = false;
+                }
+            }
+        }
+    }
+}
+
+const CAMERA_SHAKE: f32 = 1.0;
+
+/// The boss loses as much health per second as it takes seconds to vacuum a regular ghost
+fn drain_health(
+    time: Res<Time>,
+    mut stats: ResMut<Stats>,
+    mut ghost_spawn_config: ResMut<GhostSpawnConfig>,
+    mut camera_settings: ResMut<CameraSettings>,
+    mut bosses: Query<(&mut Boss, &mut Transform, Entity)>,
+    minions: Query<Entity, With<Minion>>,
+    mut commands: Commands,
+) {
+    for (mut boss, mut transform, entity) in &mut bosses {
+        if boss.in_range {
+            boss.health -= time.delta_seconds() / stats.suck_time;
+            camera_settings.translational_shake += time.delta_seconds();
+        }
+        transform.scale = Vec3::ONE * boss.definition.scale * boss.normalized_health().remap((0.0, 1.0), (0.5, 1.0));
+
+        if boss.health <= 0.0 {
+            info!("boss {:?} defeated", entity);
+            stats.capture_ghost();
+            ghost_spawn_config.eliminate_ghost();
+            commands.entity(entity).despawn_recursive();
+            for minion in &minions {
+                ghost_spawn_config.eliminate_ghost();
+                commands.entity(minion).despawn_recursive();
+            }
+            camera_settings.add(CAMERA_SHAKE);
+        }
+    }
+}
+
+const CHARGE_FACTOR: f32 = 4.0;
+
+fn move_boss(
+    time: Res<Time>,
+    players: Query<&Transform, (With<Player>, Without<Boss>)>,
+    mut bosses: Query<(&mut Transform, &Boss, Option<&mut Charging>, Entity), Without<Spawning>>,
+    mut commands: Commands,
+) {
+    let Ok(player) = players.get_single() else {
+        return;
+    };
+    for (mut transform, boss, charging, entity) in &mut bosses {
+        let direction = match charging {
+            Some(mut charging) => {
+                charging.timer.tick(time.delta());
+                if charging.timer.finished() {
+                    commands.entity(entity).remove::<Charging>();
+                }
+                charging.direction * CHARGE_FACTOR
+            }
+            None => {
+                let mut diff = player.translation - transform.translation;
+                diff.y = 0.0;
+                diff.normalize_or_zero()
+            }
+        };
+        transform.translation += direction * boss.definition.speed * time.delta_seconds();
+        let vantage = Vec3::new(player.translation.x, transform.translation.y, player.translation.z);
+        transform.look_at(vantage, Vec3::Y);
+    }
+}
+
+const CHARGE_DURATION: f32 = 1.0;
+const SUMMON_DISTANCE: f32 = 2.0;
+const PROJECTILE_LIFETIME: f32 = 5.0;
+
+fn attack(
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    mut ghost_spawn_config: ResMut<GhostSpawnConfig>,
+    players: Query<&Transform, (With<Player>, Without<Boss>)>,
+    mut bosses: Query<(&mut Boss, &Transform, Entity), Without<Spawning>>,
+    mut commands: Commands,
+) {
+    let Ok(player) = players.get_single() else {
+        return;
+    };
+    for (mut boss, transform, entity) in &mut bosses {
+        boss.attack_timer.tick(time.delta());
+        if !boss.attack_timer.just_finished() {
+            continue;
+        }
+
+        let mut direction = player.translation - transform.translation;
+        direction.y = 0.0;
+        let direction = direction.normalize_or_zero();
+
+        let attack = ATTACKS[rng.gen_range(0..ATTACKS.len())];
+        info!("boss {:?} attacks with {:?}", entity, attack);
+        match attack {
+            BossAttack::Charge => {
+                commands.entity(entity).insert(Charging {
+                    timer: Timer::from_seconds(CHARGE_DURATION, TimerMode::Once),
+                    direction,
+                });
+            }
+            BossAttack::Summon => {
+                let wave = ghost_spawn_config.wave().clone();
+                for i in 0..boss.definition.minions {
+                    let angle = i as f32 * std::f32::consts::TAU / boss.definition.minions as f32;
+                    let offset = Vec3::new(angle.sin(), 0.0, angle.cos()) * SUMMON_DISTANCE;
+                    let position = Vec3::new(transform.translation.x, -1.0, transform.translation.z) + offset;
+                    let minion = spawn_ghost(&mut commands, &asset_server, &wave, Archetype::Common, position);
+                    commands.entity(minion)
+                        .insert(Minion)
+                        .insert(Spawning(Timer::new(Duration::from_secs_f32(1.0), TimerMode::Once)));
+                }
+                ghost_spawn_config.add_extra_ghosts(boss.definition.minions);
+            }
+            BossAttack::Projectile => {
+                commands.spawn(SceneBundle {
+                    scene: asset_server.load("ghost.glb#Scene0"),
+                    transform: Transform::from_translation(transform.translation + direction)
+                        .with_scale(Vec3::ONE * 0.3)
+                        .looking_at(transform.translation + direction * 2.0, Vec3::Y),
+                    ..default()
+                })
+                .insert(Name::from("BossProjectile"))
+                .insert(Projectile {
+                    timer: Timer::from_seconds(PROJECTILE_LIFETIME, TimerMode::Once),
+                    velocity: direction * boss.definition.projectile_speed,
+                    damage: boss.definition.damage * 0.5,
+                })
+                .insert(Collider::ball(0.5))
+                .insert(Sensor)
+                .insert(CollisionGroups::new(Group::GROUP_2, Group::GROUP_1))
+                .insert(ActiveEvents::COLLISION_EVENTS);
+            }
+        }
+    }
+}
+
+fn move_projectiles(
+    time: Res<Time>,
+    mut projectiles: Query<(&mut Transform, &mut Projectile, Entity)>,
+    mut commands: Commands,
+) {
+    for (mut transform, mut projectile, entity) in &mut projectiles {
+        projectile.timer.tick(time.delta());
+        transform.translation += projectile.velocity * time.delta_seconds();
+        if projectile.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Bosses hurt the player on contact without being destroyed, projectiles vanish on impact
+fn detect_hits(
+    mut player_collisions: EventReader<CollideWithPlayer>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    bosses: Query<(&Boss, &Transform)>,
+    projectiles: Query<(&Projectile, &Transform)>,
+    players: Query<Entity, (With<Player>, Without<Invulnerable>)>,
+    mut commands: Commands,
+) {
+    for collision in player_collisions.read() {
+        if let Ok((boss, transform)) = bosses.get(collision.0) {
+            damage_events.send(DamageEvent { damage: boss.definition.damage, source: transform.translation });
+        }
+    }
+
+    for collision_event in collision_events.read() {
+        let CollisionEvent::Started(left, right, _) = collision_event else {
+            continue;
+        };
+        if !players.iter().any(|p| p == *left || p == *right) {
+            continue;
+        }
+        for entity in [*left, *right] {
+            if let Ok((projectile, transform)) = projectiles.get(entity) {
+                damage_events.send(DamageEvent { damage: projectile.damage, source: transform.translation });
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
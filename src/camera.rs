@@ -1,6 +1,7 @@
 use bevy::prelude::*;
+use rand::thread_rng;
 
-use crate::{resource::{CameraSettings, InputValues}, component::{Player, FollowCamera}, common::Random, events::DamageEvent};
+use crate::{resource::{CameraSettings, InputValues}, settings::Settings, component::{Player, FollowCamera}, common::Random, events::DamageEvent};
 
 pub struct FollowCameraPlugin;
 
@@ -29,13 +30,14 @@ fn spawn_camera(
 
 fn update_camera(
     time: Res<Time>,
+    settings: Res<Settings>,
     mut camera_settings: ResMut<CameraSettings>,
     player_query: Query<&Transform, (With<Player>, Without<FollowCamera>)>,
     mut camera_query: Query<&mut Transform, (With<FollowCamera>, Without<Player>)>,
 ) {
     if let Ok(player) = player_query.get_single() {
         for mut camera in &mut camera_query {
-            camera.translation = player.translation + camera_settings.offset + Vec3::random() * camera_settings.translational_shake * camera_settings.translational_strength;
+            camera.translation = player.translation + camera_settings.offset + Vec3::random(&mut thread_rng()) * camera_settings.translational_shake * camera_settings.translational_strength * settings.camera_shake;
             camera_settings.tick(time.delta());
         }
     }
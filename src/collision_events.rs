@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-use crate::{component::*, GameState};
+use crate::{component::*, GameSet, playing};
 
 pub struct CollisionPlugin;
 
@@ -18,7 +18,10 @@ impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<CollideWithPlayer>()
             .add_event::<SuckEvent>()
-            .add_systems(Update, handle_collisions.run_if(in_state(GameState::Game)));
+            .add_systems(Update, (
+                handle_collisions,
+                hit_ongoing_contacts,
+            ).chain().in_set(GameSet::Collisions).run_if(playing));
     }
 }
 
@@ -27,7 +30,7 @@ fn handle_collisions(
     mut player_collision: EventWriter<CollideWithPlayer>,
     mut suck_events: EventWriter<SuckEvent>,
     suckables: Query<Entity, With<Suckable>>,
-    players: Query<Entity, With<Player>>,
+    players: Query<(Entity, Has<Invulnerable>), With<Player>>,
     nozzles: Query<Entity, With<Nozzle>>,
 ) {
     for collision_event in collision_events.read() {
@@ -36,8 +39,11 @@ fn handle_collisions(
                 if nozzles.iter().any(|n| n == *left || n == *right) {
                     suck_events.send(SuckEvent::Start(suckable));
                 }
-                else if players.iter().any(|p| p == *left || p == *right) {
-                    player_collision.send(CollideWithPlayer(suckable));
+                else if let Some((_, invulnerable)) = players.iter().find(|(p, _)| p == left || p == right) {
+                    // ghosts pass through an invulnerable player
+                    if !invulnerable {
+                        player_collision.send(CollideWithPlayer(suckable));
+                    }
                 }
             }
         }
@@ -49,4 +55,26 @@ fn handle_collisions(
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Ghosts that started touching the player while it was invulnerable hurt it once the invulnerability is over
+fn hit_ongoing_contacts(
+    rapier_context: Res<RapierContext>,
+    mut removed_invulnerabilities: RemovedComponents<Invulnerable>,
+    mut player_collision: EventWriter<CollideWithPlayer>,
+    suckables: Query<Entity, With<Suckable>>,
+    players: Query<Entity, (With<Player>, Without<Invulnerable>)>,
+) {
+    for player in removed_invulnerabilities.read() {
+        if !players.contains(player) {
+            continue;
+        }
+        for (left, right, intersecting) in rapier_context.intersections_with(player) {
+            let other = if left == player { right } else { left };
+            if intersecting && suckables.contains(other) {
+                info!("{:?} is still touching the player", other);
+                player_collision.send(CollideWithPlayer(other));
+            }
+        }
+    }
+}
@@ -14,12 +14,11 @@ impl Remap for f32 {
 }
 
 pub trait Random {
-    fn random() -> Self;
+    fn random<R: Rng + ?Sized>(rng: &mut R) -> Self;
 }
 
 impl Random for Vec3 {
-    fn random() -> Self {
-        let mut rng = rand::thread_rng();
+    fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
         Self {
             x: rng.gen(),
             y: rng.gen(),
@@ -29,14 +28,12 @@ impl Random for Vec3 {
 }
 
 impl Random for Quat {
-    fn random() -> Self {
-        let mut rng = rand::thread_rng();
+    fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
         Self::from_euler(bevy::math::EulerRot::XYZ, rng.gen(), rng.gen(), rng.gen())
     }
 }
 
-pub fn point_in_circle(radius: f32) -> (f32, f32) {
-    let mut rng = rand::thread_rng();
+pub fn point_in_circle<R: Rng + ?Sized>(rng: &mut R, radius: f32) -> (f32, f32) {
     let angle = rng.gen_range(0.0..=PI*2.0);
     let random_radius = rng.gen_range(0.0..radius);
     let sin = angle.sin() * random_radius;
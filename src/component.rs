@@ -7,18 +7,49 @@ use crate::common::Remap;
 #[derive(Component)]
 pub struct Player;
 
+/// Moves the player at a fixed velocity instead of the movement input until the timer has finished
+#[derive(Component)]
+pub struct Dashing {
+    pub timer: Timer,
+    pub velocity: Vec3,
+}
+
+/// Ghosts and projectiles pass through the player until the timer has finished
+#[derive(Component)]
+pub struct Invulnerable(pub Timer);
+
+/// Velocity added to the movement of the player after a hit, it fades out quickly
+#[derive(Component)]
+pub struct Recoil(pub Vec3);
+
 #[derive(Component)]
 pub struct FollowCamera;
 
 #[derive(Component)]
 pub struct Ghost(pub f32);
 
+/// The direction a ghost moved in last, neighbouring ghosts align with it
+#[derive(Component, Default)]
+pub struct Heading(pub Vec3);
+
 #[derive(Component)]
 pub struct Suckable;
 
+/// The mass an entity has while the vacuum pulls it
+#[derive(Component)]
+pub struct Pullable(pub f32);
+
 #[derive(Component)]
 pub struct Nozzle;
 
+/// Graves and tombstones ghosts can rise out of
+#[derive(Component)]
+pub struct GhostSpawnPoint;
+
+/// The player empties the vacuum tank by walking up to one of these
+#[derive(Component)]
+pub struct DisposalPoint;
+
 #[derive(Component)]
 pub struct Damage(pub f32);
 
@@ -28,8 +59,57 @@ pub struct FloatTimer {
     height: (f32, f32)
 }
 
-#[derive(Deref, DerefMut, Component)]
-pub struct SuckTimer(pub Timer);
+/// How far an entity has been vacuumed from 0 to 1, decays again while it is outside of the vacuum range
+#[derive(Component)]
+pub struct SuckProgress {
+    pub progress: f32,
+    pub in_range: bool,
+    /// Seconds since the vacuuming started, drives the wobble independently of when the game was started
+    pub elapsed: f32,
+}
+
+/// Pushes an entity away until the timer has finished, it doesn't move on its own meanwhile
+#[derive(Component)]
+pub struct Knockback {
+    pub timer: Timer,
+    pub direction: Vec3,
+}
+
+/// Multiplies the time it takes to vacuum an entity
+#[derive(Component)]
+pub struct SuckResistance(pub f32);
+
+/// Switches between a solid and a phased out state, phased out entities can't be vacuumed
+#[derive(Component)]
+pub struct Phasing {
+    pub timer: Timer,
+    pub phased: bool,
+    solid_time: f32,
+    phased_time: f32,
+}
+
+impl Phasing {
+    pub fn new(solid_time: f32, phased_time: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(solid_time, TimerMode::Once),
+            phased: false,
+            solid_time,
+            phased_time,
+        }
+    }
+
+    /// Advances the timer and returns true if the state has changed
+    pub fn tick(&mut self, delta: Duration) -> bool {
+        self.timer.tick(delta);
+        if !self.timer.finished() {
+            return false;
+        }
+        self.phased = !self.phased;
+        let duration = if self.phased { self.phased_time } else { self.solid_time };
+        self.timer = Timer::from_seconds(duration, TimerMode::Once);
+        true
+    }
+}
 
 impl FloatTimer {
     pub fn new(height: (f32, f32)) -> Self {
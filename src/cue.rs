@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use bevy::{prelude::*, time::Real};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+
+use crate::{mixer::{Bus, Channel, Mixer}, spatial_sound::Follow};
+
+/// Plays the one shot sounds that gameplay fires as cues, with random variants and limits on how often and how many of them play
+pub struct CuePlugin;
+
+impl Plugin for CuePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayCue>()
+            .init_resource::<LastPlayed>()
+            .add_systems(Update, play_cues);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Cue {
+    Blow,
+    Overheat,
+    Cooled,
+    TankEmptied,
+    PickedUpgrade,
+    Hurt,
+    SuckPop,
+    GhostMoan,
+    GhostWhoosh,
+    ButtonPressed,
+}
+
+pub struct CueDefinition {
+    /// One of the variants is picked at random
+    pub variants: &'static [&'static str],
+    pub bus: Bus,
+    pub gain: f32,
+    /// Range the gain is randomly multiplied with
+    pub volume: (f32, f32),
+    /// Range of the random playback speed, which changes the pitch as well
+    pub pitch: (f32, f32),
+    /// Voices of the cue that play at once, cues beyond that are dropped
+    pub max_voices: usize,
+    /// Seconds before the cue plays again
+    pub cooldown: f32,
+}
+
+impl Cue {
+    pub fn definition(&self) -> CueDefinition {
+        match self {
+            Cue::Blow => CueDefinition {
+                variants: &["sounds/suck_pop.wav"],
+                bus: Bus::Sfx,
+                gain: 0.5,
+                volume: (0.9, 1.0),
+                pitch: (0.55, 0.65),
+                max_voices: 4,
+                cooldown: 0.05,
+            },
+            Cue::Overheat => CueDefinition {
+                variants: &["sounds/overheat.wav"],
+                bus: Bus::Sfx,
+                gain: 0.5,
+                volume: (1.0, 1.0),
+                pitch: (1.0, 1.0),
+                max_voices: 1,
+                cooldown: 0.0,
+            },
+            Cue::Cooled => CueDefinition {
+                variants: &["sounds/cooled_down.wav"],
+                bus: Bus::Sfx,
+                gain: 0.5,
+                volume: (1.0, 1.0),
+                pitch: (1.0, 1.0),
+                max_voices: 1,
+                cooldown: 0.0,
+            },
+            Cue::TankEmptied => CueDefinition {
+                variants: &["sounds/blop.wav"],
+                bus: Bus::Sfx,
+                gain: 0.5,
+                volume: (0.9, 1.0),
+                pitch: (0.95, 1.05),
+                max_voices: 2,
+                cooldown: 0.1,
+            },
+            Cue::PickedUpgrade => CueDefinition {
+                variants: &["sounds/blop.wav"],
+                bus: Bus::Sfx,
+                gain: 0.5,
+                volume: (1.0, 1.0),
+                pitch: (1.0, 1.0),
+                max_voices: 1,
+                cooldown: 0.0,
+            },
+            Cue::Hurt => CueDefinition {
+                variants: &["sounds/oof.wav", "sounds/oof_2.wav"],
+                bus: Bus::Sfx,
+                gain: 0.5,
+                volume: (0.85, 1.0),
+                pitch: (0.9, 1.1),
+                max_voices: 1,
+                cooldown: 0.3,
+            },
+            Cue::SuckPop => CueDefinition {
+                variants: &["sounds/suck_pop.wav"],
+                bus: Bus::Sfx,
+                gain: 0.5,
+                volume: (0.8, 1.0),
+                pitch: (0.9, 1.2),
+                max_voices: 6,
+                cooldown: 0.03,
+            },
+            Cue::GhostMoan => CueDefinition {
+                variants: &["sounds/ghost_moan.wav", "sounds/ghost_moan_2.wav"],
+                bus: Bus::Sfx,
+                gain: 0.3,
+                volume: (0.7, 1.0),
+                pitch: (0.85, 1.15),
+                max_voices: 4,
+                cooldown: 0.2,
+            },
+            Cue::GhostWhoosh => CueDefinition {
+                variants: &["sounds/vacuum_stop.wav"],
+                bus: Bus::Sfx,
+                gain: 0.3,
+                volume: (0.7, 1.0),
+                pitch: (1.3, 1.5),
+                max_voices: 3,
+                cooldown: 0.2,
+            },
+            Cue::ButtonPressed => CueDefinition {
+                variants: &["sounds/click.wav"],
+                bus: Bus::Ui,
+                gain: 0.5,
+                volume: (1.0, 1.0),
+                pitch: (0.95, 1.05),
+                max_voices: 2,
+                cooldown: 0.0,
+            },
+        }
+    }
+}
+
+/// Fires a cue, it is played as a spatial sound if it has a position
+#[derive(Event)]
+pub struct PlayCue {
+    pub cue: Cue,
+    pub position: Option<Vec3>,
+    /// The spatial sound moves along with this entity
+    pub follow: Option<Entity>,
+}
+
+impl PlayCue {
+    pub fn new(cue: Cue) -> Self {
+        Self {
+            cue,
+            position: None,
+            follow: None,
+        }
+    }
+
+    pub fn at(cue: Cue, position: Vec3) -> Self {
+        Self {
+            position: Some(position),
+            ..Self::new(cue)
+        }
+    }
+
+    pub fn following(mut self, entity: Entity) -> Self {
+        self.follow = Some(entity);
+        self
+    }
+}
+
+#[derive(Component)]
+struct Voice(Cue);
+
+/// When each cue was played last, in real time so cues of the menus keep their cooldowns while paused
+#[derive(Default, Resource)]
+struct LastPlayed(HashMap<Cue, f32>);
+
+fn play_cues(
+    time: Res<Time<Real>>,
+    asset_server: Res<AssetServer>,
+    mixer: Res<Mixer>,
+    mut last_played: ResMut<LastPlayed>,
+    mut events: EventReader<PlayCue>,
+    voices: Query<&Voice>,
+    mut commands: Commands,
+) {
+    let mut playing: HashMap<Cue, usize> = HashMap::new();
+    for voice in &voices {
+        *playing.entry(voice.0).or_default() += 1;
+    }
+    let now = time.elapsed_seconds();
+    let mut rng = thread_rng();
+
+    for event in events.read() {
+        let definition = event.cue.definition();
+        let voices = playing.entry(event.cue).or_default();
+        if *voices >= definition.max_voices {
+            continue;
+        }
+        if last_played.0.get(&event.cue).is_some_and(|last| now - last < definition.cooldown) {
+            continue;
+        }
+        let Some(variant) = definition.variants.choose(&mut rng) else {
+            continue;
+        };
+
+        let gain = definition.gain * rng.gen_range(definition.volume.0..=definition.volume.1);
+        let settings = PlaybackSettings::DESPAWN
+            .with_speed(rng.gen_range(definition.pitch.0..=definition.pitch.1))
+            .with_spatial(event.position.is_some());
+        let mut voice = commands.spawn(mixer.audio(asset_server.load(*variant), Channel::new(definition.bus, gain), settings));
+        voice.insert(Voice(event.cue));
+        if let Some(position) = event.position {
+            voice.insert(TransformBundle::from_transform(Transform::from_translation(position)));
+        }
+        if let Some(entity) = event.follow {
+            voice.insert(Follow(entity));
+        }
+
+        last_played.0.insert(event.cue, now);
+        *voices += 1;
+    }
+}
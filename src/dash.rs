@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{component::{Dashing, Invulnerable, Player}, resource::{InputValues, Stats, Headless}, GameSet, GameState, playing};
+
+/// Lets the player dash a short distance with a cooldown, ghosts can't hurt the player at the start of a dash
+pub struct DashPlugin;
+
+impl Plugin for DashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Game), (reset_cooldown, remove_all_trails))
+            .add_systems(Update, (
+                start_dash,
+                dash,
+                tick_invulnerability,
+            ).chain().in_set(GameSet::Dash).run_if(playing))
+            .add_systems(Update, (
+                spawn_trail,
+                fade_trail,
+            ).run_if(playing.and_then(not(resource_exists::<Headless>()))));
+    }
+}
+
+/// Seconds a dash lasts, its speed follows from the dash distance
+const DASH_TIME: f32 = 0.15;
+/// Seconds at the start of a dash in which ghosts pass through the player
+const DASH_IFRAMES: f32 = 0.25;
+const TRAIL_INTERVAL: f32 = 0.02;
+const TRAIL_LIFETIME: f32 = 0.3;
+
+#[derive(Resource)]
+struct DashCooldown(Timer);
+
+#[derive(Component)]
+struct DashTrail(Timer);
+
+fn reset_cooldown(
+    mut commands: Commands,
+) {
+    // an empty timer finishes on its first tick, so the first dash is ready right away
+    commands.insert_resource(DashCooldown(Timer::from_seconds(0.0, TimerMode::Once)));
+}
+
+fn remove_all_trails(
+    query: Query<Entity, With<DashTrail>>,
+    mut commands: Commands,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn start_dash(
+    time: Res<Time>,
+    stats: Res<Stats>,
+    input_values: Res<InputValues>,
+    mut cooldown: ResMut<DashCooldown>,
+    players: Query<(&Transform, Option<&Invulnerable>, Entity), (With<Player>, Without<Dashing>)>,
+    mut commands: Commands,
+) {
+    cooldown.0.tick(time.delta());
+    if !input_values.dash || !cooldown.0.finished() {
+        return;
+    }
+    let Ok((transform, invulnerable, entity)) = players.get_single() else {
+        return;
+    };
+
+    // without movement input the player dashes towards the cursor
+    let mut direction = Vec3::new(input_values.movement.x, 0.0, input_values.movement.y);
+    if direction == Vec3::ZERO {
+        direction = input_values.mouse_position - transform.translation;
+        direction.y = 0.0;
+    }
+    let direction = direction.normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return;
+    }
+
+    info!("dashing towards {:?}", direction);
+    cooldown.0 = Timer::from_seconds(stats.dash_cooldown, TimerMode::Once);
+    commands.entity(entity)
+        .insert(Dashing {
+            timer: Timer::from_seconds(DASH_TIME, TimerMode::Once),
+            velocity: direction * stats.dash_distance / DASH_TIME,
+        });
+    // a dash right after a hit must not cut its invulnerability short
+    if invulnerable.is_none_or(|i| i.0.remaining_secs() < DASH_IFRAMES) {
+        commands.entity(entity).insert(Invulnerable(Timer::from_seconds(DASH_IFRAMES, TimerMode::Once)));
+    }
+}
+
+fn dash(
+    time: Res<Time>,
+    mut players: Query<(&mut Velocity, &mut Dashing, Entity)>,
+    mut commands: Commands,
+) {
+    for (mut velocity, mut dashing, entity) in &mut players {
+        dashing.timer.tick(time.delta());
+        velocity.linvel = dashing.velocity;
+        if dashing.timer.finished() {
+            commands.entity(entity).remove::<Dashing>();
+        }
+    }
+}
+
+fn tick_invulnerability(
+    time: Res<Time>,
+    mut query: Query<(&mut Invulnerable, Entity)>,
+    mut commands: Commands,
+) {
+    for (mut invulnerable, entity) in &mut query {
+        invulnerable.0.tick(time.delta());
+        if invulnerable.0.finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+fn spawn_trail(
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut last_spawn: Local<f32>,
+    players: Query<&Transform, (With<Player>, With<Dashing>)>,
+    mut commands: Commands,
+) {
+    *last_spawn += time.delta_seconds();
+    let Ok(player) = players.get_single() else {
+        return;
+    };
+    if *last_spawn < TRAIL_INTERVAL {
+        return;
+    }
+    *last_spawn = 0.0;
+
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Capsule {
+            radius: 0.25,
+            depth: 0.5,
+            ..default()
+        })),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgba(0.6, 0.9, 1.0, 0.4),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+        transform: Transform::from_translation(player.translation + Vec3::Y * 0.5),
+        ..default()
+    })
+    .insert(Name::from("DashTrail"))
+    .insert(DashTrail(Timer::from_seconds(TRAIL_LIFETIME, TimerMode::Once)));
+}
+
+/// Trail segments shrink until they vanish
+fn fade_trail(
+    time: Res<Time>,
+    mut query: Query<(&mut Transform, &mut DashTrail, Entity)>,
+    mut commands: Commands,
+) {
+    for (mut transform, mut trail, entity) in &mut query {
+        trail.0.tick(time.delta());
+        transform.scale = Vec3::ONE * trail.0.percent_left();
+        if trail.0.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-use crate::{component::*, collision_events::*, events::*, resource::*, enemy_spawner::{GhostSpawnConfig, Spawning}, GameState, common::Remap};
+use crate::{archetype::Archetype, component::*, collision_events::*, events::*, resource::*, enemy_spawner::{GhostSpawnConfig, Spawning, spawn_ghost}, flocking::SpatialHash, navigation::{FlowField, NavGrid}, GameSet, playing, common::Remap};
 
 pub struct EnemyPlugin;
 
@@ -9,36 +9,59 @@ impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
         app
         .add_event::<DamageEvent>()
-        .add_systems(Update, (
+        .add_systems(Update, ((
             move_enemies,
             detect_collisions,
+            // a ghost that hit the player is gone before it could also be counted as vacuumed
+            apply_deferred,
             detect_suck_events,
             detect_suckage,
             rise_ghost,
-        ).run_if(in_state(GameState::Game)))
-        .add_systems(Update, float_entites);
+            phase_ghosts,
+        ).chain().run_if(playing), float_entites).chain().in_set(GameSet::Enemies));
     }
 }
 
+/// Close to the player solid ghosts go straight for them instead of following the grid cells
+const DIRECT_DISTANCE: f32 = 1.0;
+
+#[allow(clippy::type_complexity)]
 fn move_enemies(
     time: Res<Time>,
+    nav_grid: Res<NavGrid>,
+    flow_field: Res<FlowField>,
+    spatial_hash: Res<SpatialHash>,
     player_query: Query<&Transform, (With<Player>, Without<Ghost>)>,
-    mut query: Query<(&mut Transform, &Ghost), (Without<SuckTimer>, Without<Spawning>)>,
+    mut query: Query<(&mut Transform, &mut Heading, &Ghost, &Archetype, Option<&SuckProgress>, Entity), (Without<Spawning>, Without<Knockback>)>,
 ) {
-    for (mut transform, ghost) in &mut query {
-
-        let mut direction = Vec3::ZERO;
-        if let Ok(player) = player_query.get_single() {
-            let mut vantage = player.translation;
-            vantage.y = transform.translation.y;
-            transform.look_at(vantage, Vec3::Y);
-            let mut diff = player.translation - transform.translation;
-            diff.y = 0.0;
-            direction = diff.normalize_or_zero() * time.delta_seconds() * ghost.0;
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    for (mut transform, mut heading, ghost, archetype, progress, entity) in &mut query {
+        // ghosts inside the vacuum range are pulled towards the nozzle instead
+        if progress.is_some_and(|p| p.in_range) {
+            continue;
+        }
+        let stats = archetype.stats();
+        let mut diff = player.translation - transform.translation;
+        diff.y = 0.0;
+        let mut seek = diff.normalize_or_zero();
+        if stats.solid && diff.length() > DIRECT_DISTANCE {
+            if let Some(path) = flow_field.direction(&nav_grid, transform.translation) {
+                seek = path;
+            }
+        }
+
+        heading.0 = spatial_hash.steer(entity, transform.translation, seek, stats.steering);
+        transform.translation += heading.0 * time.delta_seconds() * ghost.0;
+        // the vacuum shrinks and turns ghosts until their progress has decayed
+        if progress.is_none() {
+            if heading.0 != Vec3::ZERO {
+                let vantage = transform.translation + heading.0;
+                transform.look_at(vantage, Vec3::Y);
+            }
+            transform.scale = Vec3::ONE * stats.scale;
         }
-        direction.y = 0.0;
-        transform.translation += direction;
-        transform.scale = Vec3::ONE;
     }
 }
 
@@ -64,9 +87,12 @@ fn rise_ghost(
 
 fn float_entites(
     time: Res<Time>,
-    mut query: Query<(&mut Transform, &mut FloatTimer), (Without<SuckTimer>, Without<Spawning>)>
+    mut query: Query<(&mut Transform, &mut FloatTimer, Option<&SuckProgress>), Without<Spawning>>
 ) {
-    for (mut transform, mut timer) in &mut query {
+    for (mut transform, mut timer, progress) in &mut query {
+        if progress.is_some_and(|p| p.in_range) {
+            continue;
+        }
         timer.tick(time.delta());
         let height = timer.height();
         transform.translation.y = height;
@@ -77,16 +103,22 @@ fn detect_collisions(
     mut ghost_spawn_config: ResMut<GhostSpawnConfig>,
     mut collision_events: EventReader<CollideWithPlayer>,
     mut damage_events: EventWriter<DamageEvent>,
-    damages: Query<&Damage>,
+    damages: Query<(&Damage, &Transform)>,
     mut commands: Commands,
 ) {
+    // the player is invulnerable after a hit, other ghosts touching it in the same frame hurt it once that is over
+    let mut hit = false;
     for collision_event in collision_events.read() {
+        if hit {
+            continue;
+        }
         info!("Handling colision with player for {:?}", collision_event.0);
         if let Some(entity) = commands.get_entity(collision_event.0) {
-            if let Ok(damage) = damages.get(collision_event.0) {
-                damage_events.send(DamageEvent(damage.0));
+            if let Ok((damage, transform)) = damages.get(collision_event.0) {
+                damage_events.send(DamageEvent { damage: damage.0, source: transform.translation });
                 ghost_spawn_config.eliminate_ghost();
                 entity.despawn_recursive();
+                hit = true;
             }
         }
     }
@@ -94,28 +126,40 @@ fn detect_collisions(
 
 const CAMERA_SHAKE: f32 = 0.1;
 
+const SPLIT_DISTANCE: f32 = 0.75;
+
 fn detect_suck_events(
+    asset_server: Res<AssetServer>,
     mut stats: ResMut<Stats>,
     mut ghost_spawn_config: ResMut<GhostSpawnConfig>,
     mut camera_settings: ResMut<CameraSettings>,
     mut events: EventReader<Sucked>,
-    query: Query<Entity, With<Ghost>>,
+    query: Query<(Entity, &Transform, Option<&Archetype>), With<Ghost>>,
     mut commands: Commands,
 ) {
     for event in events.read() {
         info!("Handling vacuuming of {:?}", event.0);
-        if let Ok(ghost) = query.get(event.0) {
-            stats.sucked_ghosts += 1;
+        if let Ok((ghost, transform, archetype)) = query.get(event.0) {
+            stats.capture_ghost();
             ghost_spawn_config.eliminate_ghost();
             commands.entity(ghost).despawn_recursive();
             camera_settings.add(CAMERA_SHAKE);
+
+            if archetype == Some(&Archetype::Splitter) {
+                for side in [transform.left(), transform.right()] {
+                    let position = transform.translation + side * SPLIT_DISTANCE;
+                    let id = spawn_ghost(&mut commands, &asset_server, ghost_spawn_config.wave(), Archetype::Wisp, position);
+                    info!("{:?} split into {:?}", ghost, id);
+                }
+                ghost_spawn_config.add_extra_ghosts(2);
+            }
         }
     }
 }
 
 fn detect_suckage(
     mut suck_events: EventReader<SuckEvent>,
-    query: Query<&Ghost>,
+    query: Query<Option<&Phasing>, With<Ghost>>,
     mut commands: Commands,
 ) {
     for suck_event in suck_events.read() {
@@ -124,18 +168,55 @@ fn detect_suckage(
                 info!("Started vacuuming {:?}", entity);
                 if query.contains(*entity) {
                     if let Some(mut cmds) = commands.get_entity(*entity) {
-                        cmds.insert(CollisionGroups::new(Group::GROUP_2, Group::GROUP_3));
+                        cmds.try_insert(CollisionGroups::new(Group::GROUP_2, Group::GROUP_3));
                     }
                 }
             }
             SuckEvent::Stop(entity) => {
                 info!("Stopped vacuuming {:?}", entity);
-                if query.contains(*entity) {
+                if let Ok(phasing) = query.get(*entity) {
                     if let Some(mut cmds) = commands.get_entity(*entity) {
-                        cmds.insert(CollisionGroups::new(Group::GROUP_2, Group::GROUP_1 | Group::GROUP_3));
+                        cmds.try_insert(ghost_collision_groups(phasing.is_some_and(|p| p.phased)));
                     }
                 }
             }
         }
     }
+}
+
+/// Phased out ghosts still hurt the player, but are ignored by the vacuum
+fn ghost_collision_groups(phased: bool) -> CollisionGroups {
+    if phased {
+        CollisionGroups::new(Group::GROUP_2, Group::GROUP_1)
+    }
+    else {
+        CollisionGroups::new(Group::GROUP_2, Group::GROUP_1 | Group::GROUP_3 | Group::GROUP_6)
+    }
+}
+
+const FLICKER_FREQUENCY: f32 = 10.0;
+
+fn phase_ghosts(
+    time: Res<Time>,
+    mut query: Query<(&mut Phasing, &mut Visibility, Entity), Without<Spawning>>,
+    mut commands: Commands,
+) {
+    for (mut phasing, mut visibility, entity) in &mut query {
+        if phasing.tick(time.delta()) {
+            info!("{:?} phased {}", entity, if phasing.phased { "out" } else { "in" });
+            // the ghost may have been vacuumed up or hit the player earlier in this frame
+            let mut cmds = commands.entity(entity);
+            cmds.try_insert(ghost_collision_groups(phasing.phased));
+            if phasing.phased {
+                // a phased out ghost loses its grip on the vacuum
+                cmds.remove::<SuckProgress>();
+            }
+        }
+
+        *visibility = if phasing.phased && ((phasing.timer.elapsed_secs() * FLICKER_FREQUENCY) as u32).is_multiple_of(2) {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
 }
\ No newline at end of file
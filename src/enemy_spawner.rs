@@ -1,33 +1,60 @@
 use std::time::Duration;
 
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
 use bevy_prng::ChaCha8Rng;
 use bevy_rand::resource::GlobalEntropy;
 use bevy_rapier3d::prelude::*;
+use bevy_scene_hook::{SceneHook, HookedSceneBundle};
+use rand::seq::SliceRandom;
 use rand_core::RngCore;
 
-use crate::{component::*, events::{WaveEnd, PickedUpgrade}, GameState};
+use crate::{archetype::Archetype, boss::spawn_boss, component::*, events::{WaveEnd, PickedUpgrade}, resource::Headless, waves::{BossDefinition, SpawnPattern, WaveDefinition, WaveDefinitions, Waves, WavesPlugin}, GameSet, GameState, playing};
 
 pub struct EnemySpawnerPlugin;
 
-const INITIAL_TIME_BETWEEN_GHOSTS: f32 = 0.8;
-
 impl Plugin for EnemySpawnerPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<WaveEnd>()
+        .add_plugins(WavesPlugin)
         .add_systems(OnEnter(GameState::Game), (reset_config, kill_all_ghosts))
         .add_systems(Update, (
+            apply_wave_definitions,
             spawn_enemy,
             check_wave_end,
             reset_wave,
-        ).run_if(in_state(GameState::Game)));
+        ).chain().in_set(GameSet::Waves).run_if(playing))
+        .add_systems(Update, tint_ghosts.run_if(not(resource_exists::<Headless>())));
     }
 }
 
 fn reset_config(
+    waves: Res<Waves>,
+    wave_definitions: Res<Assets<WaveDefinitions>>,
     mut commands: Commands,
 ) {
-    commands.insert_resource(GhostSpawnConfig::new());
+    let mut config = GhostSpawnConfig::new(WaveDefinition::default());
+    if let Some(definitions) = wave_definitions.get(&waves.0) {
+        config.set_wave(definitions.wave(1), definitions.boss(1));
+    }
+    commands.insert_resource(config);
+}
+
+/// Picks up the wave definitions once they are loaded or whenever they are changed on disk
+fn apply_wave_definitions(
+    waves: Res<Waves>,
+    wave_definitions: Res<Assets<WaveDefinitions>>,
+    mut asset_events: EventReader<AssetEvent<WaveDefinitions>>,
+    mut config: ResMut<GhostSpawnConfig>,
+) {
+    for asset_event in asset_events.read() {
+        if asset_event.is_loaded_with_dependencies(&waves.0) || asset_event.is_modified(&waves.0) {
+            if let Some(definitions) = wave_definitions.get(&waves.0) {
+                info!("applying wave definitions to wave {}", config.current_wave);
+                let (wave, boss) = (definitions.wave(config.current_wave), definitions.boss(config.current_wave));
+                config.set_wave(wave, boss);
+            }
+        }
+    }
 }
 
 fn kill_all_ghosts(
@@ -39,34 +66,62 @@ fn kill_all_ghosts(
     }
 }
 
-#[derive(Resource)]
+#[derive(Debug, Resource)]
 pub struct GhostSpawnConfig {
     timer: Timer,
-    current_time_between_ghosts: f32,
-    damage: f32,
-    speed: f32,
-    wave_size: u32,
+    wave: WaveDefinition,
+    boss: Option<BossDefinition>,
     spawned_ghosts: u32,
+    extra_ghosts: u32,
     eliminated_ghosts: u32,
     current_wave: u32,
 }
 
 impl GhostSpawnConfig {
-    pub fn new() -> Self {
+    pub fn new(wave: WaveDefinition) -> Self {
         Self {
-            timer: Timer::new(Duration::from_secs_f32(INITIAL_TIME_BETWEEN_GHOSTS), TimerMode::Repeating),
-            current_time_between_ghosts: INITIAL_TIME_BETWEEN_GHOSTS,
-            damage: 8.0,
-            speed: 2.0,
-            wave_size: 25,
+            timer: Timer::new(Duration::from_secs_f32(wave.spawn_interval), TimerMode::Repeating),
+            wave,
+            boss: None,
             spawned_ghosts: 0,
+            extra_ghosts: 0,
             eliminated_ghosts: 0,
             current_wave: 1,
         }
     }
 
+    /// Replaces the definition of the running wave, keeping track of the ghosts already spawned
+    pub fn set_wave(&mut self, wave: WaveDefinition, boss: Option<BossDefinition>) {
+        self.timer = Timer::new(Duration::from_secs_f32(wave.spawn_interval), TimerMode::Repeating);
+        self.wave = wave;
+        self.boss = boss;
+    }
+
+    /// The number of ghosts spawned by the spawner during this wave, a boss wave only spawns the boss
+    fn ghost_count(&self) -> u32 {
+        if self.boss.is_some() {
+            1
+        }
+        else {
+            self.wave.ghost_count
+        }
+    }
+
     pub fn wave_size(&self) -> u32 {
-        self.wave_size
+        self.ghost_count() + self.extra_ghosts
+    }
+
+    pub fn is_boss_wave(&self) -> bool {
+        self.boss.is_some()
+    }
+
+    pub fn wave(&self) -> &WaveDefinition {
+        &self.wave
+    }
+
+    /// Ghosts that split off others or are summoned have to be eliminated as well before the wave ends
+    pub fn add_extra_ghosts(&mut self, count: u32) {
+        self.extra_ghosts += count;
     }
 
     pub fn eliminate_ghost(&mut self) {
@@ -81,54 +136,187 @@ impl GhostSpawnConfig {
 #[derive(Component)]
 pub struct Spawning(pub Timer);
 
+const BOSS_DISTANCE: f32 = 12.0;
+const MIN_GRAVE_DISTANCE: f32 = 8.0;
+
+/// A grave can be used if the player can't see it or if it is far enough away
+fn is_valid_grave(
+    grave: Vec3,
+    player: Vec3,
+    camera: Option<(&Camera, &GlobalTransform)>,
+) -> bool {
+    if grave.distance(player) >= MIN_GRAVE_DISTANCE {
+        return true;
+    }
+    let Some((camera, camera_transform)) = camera else {
+        return false;
+    };
+    let (Some(viewport_position), Some(viewport_size)) = (camera.world_to_viewport(camera_transform, grave), camera.logical_viewport_size()) else {
+        return false;
+    };
+    viewport_position.x < 0.0
+        || viewport_position.y < 0.0
+        || viewport_position.x > viewport_size.x
+        || viewport_position.y > viewport_size.y
+}
+
+#[allow(clippy::too_many_arguments)]
 fn spawn_enemy(
     time: Res<Time>,
     asset_server: Res<AssetServer>,
     mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
     mut config: ResMut<GhostSpawnConfig>,
     query: Query<&Transform, With<Player>>,
+    graves: Query<&GlobalTransform, With<GhostSpawnPoint>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<FollowCamera>>,
     mut commands: Commands
 ) {
     config.timer.tick(time.delta());
 
-    if config.timer.finished() && config.spawned_ghosts < config.wave_size {
-        let mut pos = Vec3::new(5.0, -1.0, 5.0);
+    if !config.timer.finished() || config.spawned_ghosts >= config.ghost_count() {
+        return;
+    }
 
+    let angle = rng.next_u32() as f32 * 100.0;
+
+    if let Some(boss) = &config.boss {
+        let mut pos = Vec3::new(angle.sin() * BOSS_DISTANCE, -1.0, angle.cos() * BOSS_DISTANCE);
         if let Ok(player) = query.get_single() {
-            let angle = rng.next_u32() as f32 * 100.0;
-            let radius = 10.0;
-            pos.x = angle.sin() * radius + player.translation.x;
-            pos.z = angle.cos() * radius + player.translation.z;
+            pos += Vec3::new(player.translation.x, 0.0, player.translation.z);
         }
-        let id = commands.spawn(SceneBundle {
-            scene: asset_server.load("ghost.glb#Scene0"),
-            transform: Transform::from_translation(pos),
-            ..default()
+        let id = spawn_boss(&mut commands, &asset_server, boss, pos);
+        info!("spawned boss {:?}", id);
+        config.spawned_ghosts += 1;
+        return;
+    }
+
+    let mut positions: Vec<Vec3> = config.wave.spawn_pattern
+        .offsets(angle)
+        .into_iter()
+        .map(|offset| {
+            let mut pos = Vec3::new(5.0, -1.0, 5.0);
+            if let Ok(player) = query.get_single() {
+                pos.x = offset.x + player.translation.x;
+                pos.z = offset.z + player.translation.z;
+            }
+            pos
         })
-        .insert(Name::from("Ghost"))
-        .insert(Ghost(config.speed))
-        .insert(Collider::capsule(Vec3::Y / -4.0, Vec3::Y / 4.0, 0.25))
-        //.insert(RigidBody::KinematicPositionBased)
-        .insert(Sensor)
-        .insert(CollisionGroups::new(Group::GROUP_2, Group::GROUP_1 | Group::GROUP_3))
-        .insert(ActiveEvents::COLLISION_EVENTS)
-        .insert(FloatTimer::new((0.5, 1.5)))
-        .insert(Damage(config.damage))
-        .insert(Suckable)
-        .insert(Spawning(Timer::new(Duration::from_secs_f32(2.0), TimerMode::Once)))
-        .id();
-
-        info!("spawned {:?}", id);
+        .collect();
+
+    if let (SpawnPattern::Graves { size }, Ok(player)) = (&config.wave.spawn_pattern, query.get_single()) {
+        let camera = cameras.get_single().ok();
+        let candidates: Vec<Vec3> = graves
+            .iter()
+            .map(|grave| grave.translation())
+            .filter(|grave| is_valid_grave(*grave, player.translation, camera))
+            .collect();
+        if !candidates.is_empty() {
+            positions = candidates
+                .choose_multiple(&mut *rng, *size as usize)
+                .map(|grave| Vec3::new(grave.x, -1.0, grave.z))
+                .collect();
+        }
+    }
+
+    for pos in positions {
+        if config.spawned_ghosts >= config.ghost_count() {
+            break;
+        }
+
+        let archetype = config.wave.pick_archetype(&mut *rng);
+        let id = spawn_ghost(&mut commands, &asset_server, &config.wave, archetype, pos);
+        commands.entity(id)
+            .insert(Spawning(Timer::new(Duration::from_secs_f32(2.0), TimerMode::Once)));
+
+        info!("spawned {:?} as {:?}", id, archetype);
 
         config.spawned_ghosts += 1;
     }
 }
 
+/// Spawns a ghost of the given archetype with the stats of the given wave
+pub fn spawn_ghost(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    wave: &WaveDefinition,
+    archetype: Archetype,
+    position: Vec3,
+) -> Entity {
+    let stats = archetype.stats();
+    let mut ghost = commands.spawn(HookedSceneBundle {
+        scene: SceneBundle {
+            scene: asset_server.load("ghost.glb#Scene0"),
+            transform: Transform::from_translation(position).with_scale(Vec3::ONE * stats.scale),
+            ..default()
+        },
+        hook: SceneHook::new(move |entity, cmds| {
+            if entity.contains::<Handle<StandardMaterial>>() {
+                cmds.insert(ArchetypeMaterial(archetype));
+            }
+        }),
+    });
+    ghost
+    .insert(Name::from("Ghost"))
+    .insert(Ghost(wave.speed * stats.speed))
+    .insert(archetype)
+    .insert(Heading::default())
+    .insert(Collider::capsule(Vec3::Y / -4.0, Vec3::Y / 4.0, 0.25))
+    .insert(RigidBody::KinematicPositionBased)
+    .insert(Sensor)
+    .insert(CollisionGroups::new(Group::GROUP_2, Group::GROUP_1 | Group::GROUP_3 | Group::GROUP_6))
+    .insert(ActiveEvents::COLLISION_EVENTS)
+    .insert(FloatTimer::new((0.5, 1.5)))
+    .insert(Damage(wave.damage * stats.damage))
+    .insert(SuckResistance(stats.suck_resistance))
+    .insert(Pullable(stats.mass))
+    .insert(Suckable);
+
+    if archetype == Archetype::Phantom {
+        ghost.insert(Phasing::new(1.5, 1.0));
+    }
+
+    ghost.id()
+}
+
+/// Marks the meshes of a ghost model, their material is replaced by the tinted one of the archetype
+#[derive(Component)]
+struct ArchetypeMaterial(Archetype);
+
+/// All ghosts of an archetype share one material, which is made from the material of the model the first time it is seen
+fn tint_ghosts(
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut tinted: Local<HashMap<Archetype, Handle<StandardMaterial>>>,
+    mut meshes: Query<(&ArchetypeMaterial, &mut Handle<StandardMaterial>), Added<ArchetypeMaterial>>,
+) {
+    for (ArchetypeMaterial(archetype), mut material) in &mut meshes {
+        if let Some(handle) = tinted.get(archetype) {
+            *material = handle.clone();
+            continue;
+        }
+        let Some(mut tinted_material) = materials.get(&*material).cloned() else {
+            continue;
+        };
+        let tint = archetype.stats().tint;
+        tinted_material.base_color = tint_color(tinted_material.base_color, tint);
+        tinted_material.emissive = tint_color(tinted_material.emissive, tint);
+        let handle = materials.add(tinted_material);
+        tinted.insert(*archetype, handle.clone());
+        *material = handle;
+    }
+}
+
+/// Multiplies the color channels in linear space and keeps the alpha of the color
+fn tint_color(color: Color, tint: Color) -> Color {
+    let [r, g, b, a] = color.as_linear_rgba_f32();
+    let [tr, tg, tb, _] = tint.as_linear_rgba_f32();
+    Color::rgba_linear(r * tr, g * tg, b * tb, a)
+}
+
 fn check_wave_end(
     mut config: ResMut<GhostSpawnConfig>,
     mut wave_end_events: EventWriter<WaveEnd>,
 ) {
-    if config.eliminated_ghosts == config.wave_size {
+    if config.eliminated_ghosts == config.wave_size() {
         info!("wave {} ended", config.current_wave);
         wave_end_events.send(WaveEnd);
         config.eliminated_ghosts = 0;
@@ -136,18 +324,20 @@ fn check_wave_end(
 }
 
 fn reset_wave(
+    waves: Res<Waves>,
+    wave_definitions: Res<Assets<WaveDefinitions>>,
     mut config: ResMut<GhostSpawnConfig>,
     mut picked_upgrade_events: EventReader<PickedUpgrade>,
 ) {
     for _ in picked_upgrade_events.read() {
         info!("resetting wave {}", config.current_wave);
-        config.wave_size = (config.wave_size as f32 * 1.1) as u32;
-        config.spawned_ghosts = 0;
-        config.damage *= 1.1;
-        config.speed *= 1.1;
-        config.current_time_between_ghosts *= 0.9;
-        config.timer = Timer::new(Duration::from_secs_f32(config.current_time_between_ghosts), TimerMode::Repeating);
-        config.timer.reset();
         config.current_wave += 1;
+        config.spawned_ghosts = 0;
+        config.extra_ghosts = 0;
+        let (wave, boss) = match wave_definitions.get(&waves.0) {
+            Some(definitions) => (definitions.wave(config.current_wave), definitions.boss(config.current_wave)),
+            None => (WaveDefinition::default(), None),
+        };
+        config.set_wave(wave, boss);
     }
 }
\ No newline at end of file
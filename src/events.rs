@@ -1,12 +1,33 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
+/// The player has been hit, the source is where the hit came from
 #[derive(Event)]
-pub struct DamageEvent(pub f32);
+pub struct DamageEvent {
+    pub damage: f32,
+    pub source: Vec3,
+}
 
-#[derive(Event)]
+#[derive(Clone, Copy, Debug, Deserialize, Event, Serialize)]
 pub enum VacuumEvent {
     Start,
     Stop,
+    /// Shoots a captured ghost out of the nozzle
+    Blow,
+}
+
+#[derive(Event)]
+pub enum HeatEvent {
+    Overheated,
+    Cooled,
+}
+
+/// Sent when the tank has been emptied with the points the captured ghosts scored
+#[derive(Event)]
+pub struct TankEmptied {
+    pub ghosts: u32,
+    pub multiplier: f32,
+    pub score: u32,
 }
 
 #[derive(Event)]
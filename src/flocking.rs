@@ -0,0 +1,107 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{component::{Ghost, Heading}, playing};
+
+/// Buckets ghosts into a spatial hash every frame so they can steer away from their neighbours
+pub struct FlockingPlugin;
+
+impl Plugin for FlockingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpatialHash>()
+            .add_systems(PreUpdate, rebuild_spatial_hash.run_if(playing));
+    }
+}
+
+/// Ghosts further apart than this don't influence each other
+pub const NEIGHBOUR_RADIUS: f32 = 1.5;
+
+/// How strongly a ghost keeps its distance, follows the crowd and goes for the player
+#[derive(Clone, Copy, Debug)]
+pub struct SteeringWeights {
+    pub separation: f32,
+    pub alignment: f32,
+    pub seek: f32,
+}
+
+struct Boid {
+    entity: Entity,
+    position: Vec3,
+    heading: Vec3,
+}
+
+/// Ghost positions bucketed into square cells of the neighbour radius
+#[derive(Default, Resource)]
+pub struct SpatialHash {
+    cells: HashMap<IVec2, Vec<Boid>>,
+}
+
+impl SpatialHash {
+    fn cell_of(position: Vec3) -> IVec2 {
+        IVec2::new(
+            (position.x / NEIGHBOUR_RADIUS).floor() as i32,
+            (position.z / NEIGHBOUR_RADIUS).floor() as i32,
+        )
+    }
+
+    /// Calls `f` with the position and heading of every other ghost within the neighbour radius
+    fn for_each_neighbour(&self, entity: Entity, position: Vec3, mut f: impl FnMut(Vec3, Vec3)) {
+        let center = Self::cell_of(position);
+        for x in -1..=1 {
+            for z in -1..=1 {
+                let Some(boids) = self.cells.get(&(center + IVec2::new(x, z))) else {
+                    continue;
+                };
+                for boid in boids {
+                    if boid.entity != entity && boid.position.distance_squared(position) < NEIGHBOUR_RADIUS * NEIGHBOUR_RADIUS {
+                        f(boid.position, boid.heading);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Blends the direction towards the player with separation from and alignment with nearby ghosts
+    pub fn steer(&self, entity: Entity, position: Vec3, seek: Vec3, weights: SteeringWeights) -> Vec3 {
+        let mut separation = Vec3::ZERO;
+        let mut alignment = Vec3::ZERO;
+        let mut neighbours = 0;
+        self.for_each_neighbour(entity, position, |other, heading| {
+            let mut away = position - other;
+            away.y = 0.0;
+            let distance = away.length();
+            // ghosts on the exact same spot push apart along an arbitrary but stable axis
+            let away = if distance > f32::EPSILON { away / distance } else { Vec3::X };
+            separation += away * (1.0 - distance / NEIGHBOUR_RADIUS);
+            alignment += heading;
+            neighbours += 1;
+        });
+        if neighbours > 0 {
+            alignment = (alignment / neighbours as f32).normalize_or_zero();
+        }
+
+        let steering = seek * weights.seek + separation * weights.separation + alignment * weights.alignment;
+        let steering = Vec3::new(steering.x, 0.0, steering.z).normalize_or_zero();
+        if steering == Vec3::ZERO {
+            seek
+        }
+        else {
+            steering
+        }
+    }
+}
+
+fn rebuild_spatial_hash(
+    mut spatial_hash: ResMut<SpatialHash>,
+    ghosts: Query<(Entity, &Transform, &Heading), With<Ghost>>,
+) {
+    for boids in spatial_hash.cells.values_mut() {
+        boids.clear();
+    }
+    for (entity, transform, heading) in &ghosts {
+        spatial_hash.cells
+            .entry(SpatialHash::cell_of(transform.translation))
+            .or_default()
+            .push(Boid { entity, position: transform.translation, heading: heading.0 });
+    }
+    spatial_hash.cells.retain(|_, boids| !boids.is_empty());
+}
@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, app::{AppExit, ScheduleRunnerPlugin}, asset::AssetMetaCheck, core::FrameCount, ecs::schedule::ExecutorKind, scene::ScenePlugin, time::TimeUpdateStrategy};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    blow::BlowPlugin,
+    boss::{Boss, BossPlugin},
+    collision_events::CollisionPlugin,
+    component::{Ghost, Player, Nozzle},
+    dash::DashPlugin,
+    enemy::EnemyPlugin,
+    enemy_spawner::{EnemySpawnerPlugin, GhostSpawnConfig},
+    events::VacuumEvent,
+    flocking::FlockingPlugin,
+    input::{InputPlugin, InputSet},
+    map_generation::MapGeneratorPlugin,
+    navigation::NavigationPlugin,
+    pause::PausePlugin,
+    player::{PlayerPlugin, spawn_nozzle},
+    random::{RandomPlugin, RngSeed},
+    replay::{ReplayPlugin, ReplayPlayback, start_game},
+    resource::*,
+    tank::TankPlugin,
+    upgrade::{UpgradePlugin, Upgrade},
+    vacuum::VacuumPlugin,
+    GameSet,
+    GameState,
+    PauseState,
+};
+
+/// Settings for a simulation run without window, renderer, audio or ui
+#[derive(Clone, Debug, Resource)]
+pub struct HeadlessConfig {
+    pub timestep: Duration,
+    pub max_frames: u32,
+    pub max_waves: u32,
+}
+
+impl HeadlessConfig {
+    /// Reads the headless settings from the command line, returns `None` if `--headless` was not passed
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        if !args.iter().any(|arg| arg == "--headless") {
+            return None;
+        }
+
+        let mut config = Self::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--timestep" => {
+                    if let Some(value) = iter.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.timestep = Duration::from_secs_f32(value);
+                    }
+                }
+                "--frames" => {
+                    if let Some(value) = iter.next().and_then(|v| v.parse().ok()) {
+                        config.max_frames = value;
+                    }
+                }
+                "--waves" => {
+                    if let Some(value) = iter.next().and_then(|v| v.parse().ok()) {
+                        config.max_waves = value;
+                    }
+                }
+                _ => (),
+            }
+        }
+        Some(config)
+    }
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        Self {
+            timestep: Duration::from_secs_f32(1.0 / 60.0),
+            max_frames: 60 * 60 * 10,
+            max_waves: 5,
+        }
+    }
+}
+
+/// Runs the game loop with all gameplay plugins but without rendering, audio and ui
+pub struct HeadlessPlugin(pub HeadlessConfig);
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AssetMetaCheck::Never)
+            .insert_resource(Headless)
+            .insert_resource(self.0.clone())
+            .insert_resource(InputValues::new())
+            .insert_resource(CameraSettings::new())
+            .insert_resource(TimeUpdateStrategy::ManualDuration(self.0.timestep))
+            .add_plugins(ReplayPlugin)
+            .edit_schedule(Update, |schedule| {
+                schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            });
+
+        // a replay brings its own frame times, so rapier has to follow them, and starts the game itself
+        if !app.world.contains_resource::<ReplayPlayback>() {
+            app.add_systems(Update, start_game.run_if(in_state(GameState::Menu)))
+                .insert_resource(RapierConfiguration {
+                    timestep_mode: TimestepMode::Fixed {
+                        dt: self.0.timestep.as_secs_f32(),
+                        substeps: 1,
+                    },
+                    ..default()
+                });
+        }
+
+        app.add_plugins((
+                MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)),
+                AssetPlugin::default(),
+                // a replay is read by the same system as the devices, which expects their resources to exist
+                bevy::input::InputPlugin,
+                TransformPlugin,
+                HierarchyPlugin,
+                ScenePlugin,
+                RapierPhysicsPlugin::<NoUserData>::default(),
+                RandomPlugin,
+            ))
+            // rapier needs the mesh assets to build colliders from scenes
+            .init_asset::<Mesh>()
+            .add_plugins((
+                InputPlugin,
+                PlayerPlugin,
+                MapGeneratorPlugin,
+                NavigationPlugin,
+                FlockingPlugin,
+                EnemyPlugin,
+                BossPlugin,
+                CollisionPlugin,
+                EnemySpawnerPlugin,
+                UpgradePlugin,
+                VacuumPlugin,
+                TankPlugin,
+                BlowPlugin,
+                DashPlugin,
+                PausePlugin,
+            ))
+            .add_state::<GameState>()
+            // gameplay systems check the pause state, even though a headless game is never paused
+            .add_state::<PauseState>()
+            .configure_sets(Update, GameSet::chained())
+            .add_systems(Update, attach_nozzle.run_if(in_state(GameState::Game)))
+            .add_systems(PreUpdate, autopilot.in_set(InputSet).run_if(not(resource_exists::<ReplayPlayback>())))
+            // a recording is saved in `Last` when the app exits, so the exit has to be sent before
+            .add_systems(PostUpdate, report);
+    }
+}
+
+pub fn run(config: HeadlessConfig) {
+    App::new()
+        .add_plugins(HeadlessPlugin(config))
+        .run();
+}
+
+/// The nozzle is usually spawned by the character scene, which is never loaded without a renderer
+fn attach_nozzle(
+    players: Query<Entity, Added<Player>>,
+    mut commands: Commands,
+) {
+    for player in &players {
+        commands.entity(player).with_children(|parent| {
+            // the nozzle collider points down, so we tilt it forward
+            spawn_nozzle(parent, Transform::from_xyz(0.0, 0.5, 0.0).with_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)));
+        });
+    }
+}
+
+const PICKUP_DISTANCE: f32 = 1.5;
+
+/// Aims at the closest ghost or boss and keeps the vacuum running, walks towards upgrades between waves
+/// and dumps the tank whenever it is full
+#[allow(clippy::type_complexity)]
+fn autopilot(
+    stats: Res<Stats>,
+    mut input_values: ResMut<InputValues>,
+    mut vacuum_events: EventWriter<VacuumEvent>,
+    players: Query<&Transform, With<Player>>,
+    // bosses have no `Ghost` component
+    ghosts: Query<&Transform, Or<(With<Ghost>, With<Boss>)>>,
+    upgrades: Query<&Transform, With<Upgrade>>,
+    nozzles: Query<(), With<Nozzle>>,
+) {
+    let Ok(player) = players.get_single() else {
+        return;
+    };
+    if nozzles.is_empty() {
+        return;
+    }
+
+    if !input_values.mouse_pressed {
+        input_values.mouse_pressed = true;
+        vacuum_events.send(VacuumEvent::Start);
+    }
+    input_values.dumping = stats.tank_full();
+
+    let closest = |transforms: Vec<Vec3>| {
+        transforms
+            .into_iter()
+            .min_by(|a, b| a.distance_squared(player.translation).total_cmp(&b.distance_squared(player.translation)))
+    };
+
+    input_values.movement = Vec2::ZERO;
+    if let Some(ghost) = closest(ghosts.iter().map(|t| t.translation).collect()) {
+        input_values.mouse_position = Vec3::new(ghost.x, 0.0, ghost.z);
+    }
+    else if let Some(upgrade) = closest(upgrades.iter().map(|t| t.translation).collect()) {
+        input_values.mouse_position = Vec3::new(upgrade.x, 0.0, upgrade.z);
+        let diff = upgrade - player.translation;
+        if diff.length() > PICKUP_DISTANCE {
+            input_values.movement = Vec2::new(diff.x, diff.z).normalize_or_zero();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn report(
+    frames: Res<FrameCount>,
+    config: Res<HeadlessConfig>,
+    seed: Res<RngSeed>,
+    game_state: Res<State<GameState>>,
+    stats: Option<Res<Stats>>,
+    ghost_spawn_config: Option<Res<GhostSpawnConfig>>,
+    playback: Option<Res<ReplayPlayback>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let (Some(stats), Some(ghost_spawn_config)) = (stats, ghost_spawn_config) else {
+        return;
+    };
+
+    let game_over = *game_state.get() == GameState::GameOver;
+    let finished = ghost_spawn_config.current_wave() > config.max_waves
+        || playback.is_some_and(|playback| playback.finished());
+    if game_over || finished || frames.0 >= config.max_frames {
+        println!("simulation with seed {} ended after {} frames", seed.0, frames.0);
+        println!("{:#?}", *stats);
+        println!("{:#?}", *ghost_spawn_config);
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Runs a headless game until it reports its end
+    pub fn play(app: &mut App) {
+        app.finish();
+        app.cleanup();
+        while app.world.resource::<Events<AppExit>>().is_empty() {
+            app.update();
+        }
+    }
+
+    #[test]
+    fn plays_a_wave() {
+        let mut app = App::new();
+        app.insert_resource(RngSeed(7))
+            .add_plugins(HeadlessPlugin(HeadlessConfig { max_waves: 1, ..default() }));
+        play(&mut app);
+
+        assert_eq!(*app.world.resource::<State<GameState>>().get(), GameState::Game);
+        assert_eq!(app.world.resource::<GhostSpawnConfig>().current_wave(), 2);
+        assert!(app.world.resource::<Stats>().sucked_ghosts > 0);
+    }
+}
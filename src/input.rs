@@ -1,24 +1,50 @@
-use bevy::{prelude::*, window::PrimaryWindow, input::{mouse::MouseButtonInput, ButtonState}};
+use bevy::{prelude::*, window::PrimaryWindow, input::{mouse::MouseButtonInput, ButtonState, InputSystem}};
 
-use crate::{resource::InputValues, component::FollowCamera, events::VacuumEvent, GameState};
+use crate::{resource::{InputValues, Headless}, component::FollowCamera, events::VacuumEvent, replay::ReplayPlayback, GameState, playing};
 
 pub struct InputPlugin;
 
+/// Fills the [`InputValues`] before any gameplay system reads them
+#[derive(Clone, Debug, Eq, Hash, PartialEq, SystemSet)]
+pub struct InputSet;
+
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<VacuumEvent>()
-            .add_systems(Update, update_values.run_if(in_state(GameState::Game)));
+            .configure_sets(PreUpdate, InputSet.after(InputSystem).run_if(playing))
+            .add_systems(OnEnter(GameState::Game), reset_values)
+            // headless games are steered by the autopilot unless they play back a replay
+            .add_systems(PreUpdate, update_values.in_set(InputSet).run_if(resource_exists::<ReplayPlayback>().or_else(not(resource_exists::<Headless>()))));
     }
 }
 
+fn reset_values(
+    mut commands: Commands,
+) {
+    commands.insert_resource(InputValues::new());
+}
+
+#[allow(clippy::too_many_arguments)]
 fn update_values(
     keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    playback: Option<ResMut<ReplayPlayback>>,
     mut input_values: ResMut<InputValues>,
     mut mouse_events: EventReader<MouseButtonInput>,
     mut vacuum_events: EventWriter<VacuumEvent>,
     windows: Query<&Window, With<PrimaryWindow>>,
     cameras: Query<(&Camera, &GlobalTransform), With<FollowCamera>>,
 ) {
+    // a replay takes the place of keyboard, mouse and gamepads
+    if let Some(mut playback) = playback {
+        if let Some(frame) = playback.next_frame() {
+            frame.apply(&mut input_values);
+            vacuum_events.send_batch(frame.vacuum_events.iter().copied());
+        }
+        return;
+    }
+
     let mut movement = Vec2::ZERO;
     if keys.pressed(KeyCode::A) {
         movement.x += -1.0;
@@ -34,6 +60,9 @@ fn update_values(
     }
 
     input_values.movement = movement.normalize_or_zero();
+    input_values.dumping = keys.pressed(KeyCode::E);
+    input_values.dash = keys.just_pressed(KeyCode::Space)
+        || gamepads.iter().any(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::South)));
 
     for event in mouse_events.read() {
         match event.state {
@@ -42,6 +71,9 @@ fn update_values(
                     input_values.mouse_pressed = true;
                     vacuum_events.send(VacuumEvent::Start);
                 }
+                if event.button == MouseButton::Right {
+                    vacuum_events.send(VacuumEvent::Blow);
+                }
             }
             ButtonState::Released => {
                 if event.button == MouseButton::Left {
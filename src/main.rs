@@ -1,38 +1,69 @@
-use bevy::{prelude::*, asset::AssetMetaCheck, window::PresentMode};
+
+use bevy::{prelude::*, asset::AssetMetaCheck, audio::{AudioPlugin, SpatialScale}, ecs::schedule::SystemSetConfigs};
 //use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_rapier3d::prelude::*;
-use bevy_rand::prelude::*;
-use bevy_prng::ChaCha8Rng;
 use bevy_scene_hook::HookPlugin;
 
+use blow::BlowPlugin;
+use boss::BossPlugin;
 use camera::FollowCameraPlugin;
 use collision_events::CollisionPlugin;
+use cue::CuePlugin;
+use dash::DashPlugin;
 use enemy::EnemyPlugin;
 use enemy_spawner::EnemySpawnerPlugin;
+use flocking::FlockingPlugin;
+use headless::HeadlessConfig;
 use input::InputPlugin;
 use map_generation::MapGeneratorPlugin;
+use mixer::MixerPlugin;
+use music::MusicPlugin;
+use navigation::NavigationPlugin;
+use pause::PausePlugin;
 use player::PlayerPlugin;
+use random::RandomPlugin;
+use replay::ReplayPlugin;
 use resource::*;
+use settings::{Settings, SettingsPlugin};
 use sound::SoundPlugin;
+use spatial_sound::{SpatialSoundPlugin, SPATIAL_SCALE};
+use tank::TankPlugin;
 use ui::UiPlugin;
 use upgrade::UpgradePlugin;
 use vacuum::VacuumPlugin;
 
+mod archetype;
+mod blow;
+mod boss;
 mod camera;
 mod collision_events;
 mod common;
 mod component;
+mod cue;
+mod dash;
 mod enemy_spawner;
 mod enemy;
 mod events;
+mod flocking;
+mod headless;
 mod input;
 mod map_generation;
+mod mixer;
+mod music;
+mod navigation;
+mod pause;
 mod player;
+mod random;
+mod replay;
 mod resource;
+mod settings;
 mod sound;
+mod spatial_sound;
+mod tank;
 mod ui;
 mod upgrade;
 mod vacuum;
+mod waves;
 
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, States)]
 enum GameState {
@@ -40,12 +71,75 @@ enum GameState {
     Menu,
     Game,
     GameOver,
+    /// Passed through on the way back into [`GameState::Game`], so a restart leaves and enters it like any other game
+    Restarting,
+}
+
+/// Whether a game is paused, it stays `Running` outside of [`GameState::Game`]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, States)]
+enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+/// The steps of a game frame in the order they run, so events are handled in the frame they are sent
+/// and a replay plays out the same no matter in which order the schedule would put them otherwise
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, SystemSet)]
+enum GameSet {
+    Collisions,
+    Vacuum,
+    Blow,
+    Tank,
+    Navigation,
+    Enemies,
+    Boss,
+    Upgrades,
+    Waves,
+    Dash,
+    Player,
+}
+
+impl GameSet {
+    fn chained() -> SystemSetConfigs {
+        (
+            Self::Collisions,
+            Self::Vacuum,
+            Self::Blow,
+            Self::Tank,
+            Self::Navigation,
+            Self::Enemies,
+            Self::Boss,
+            Self::Upgrades,
+            Self::Waves,
+            Self::Dash,
+            Self::Player,
+        ).chain()
+    }
+}
+
+/// Run condition for gameplay systems, which stop while the game is paused
+fn playing(
+    game_state: Res<State<GameState>>,
+    pause_state: Res<State<PauseState>>,
+) -> bool {
+    *game_state.get() == GameState::Game && *pause_state.get() == PauseState::Running
 }
 
 fn main() {
+    if let Some(config) = HeadlessConfig::from_args() {
+        headless::run(config);
+        return;
+    }
+
+    let settings = Settings::load();
+    let (width, height) = settings.resolution();
+    let (mode, present_mode) = (settings.window_mode(), settings.present_mode());
+
     App::new()
         .insert_resource(AssetMetaCheck::Never)
         .insert_resource(InputValues::new())
+        .insert_resource(settings)
         .insert_resource(CameraSettings {
             offset: Vec3 { x: 0.0, y: 10.0, z: 10.0 },
             translational_strength: 1.5,
@@ -56,18 +150,23 @@ fn main() {
             DefaultPlugins.set(WindowPlugin {
                 primary_window: Some(Window {
                     title: "Ghost Suckers!".into(),
-                    resolution: (1024.0, 576.0).into(),
-                    present_mode: PresentMode::AutoVsync,
+                    resolution: (width, height).into(),
+                    mode,
+                    present_mode,
                     fit_canvas_to_parent: true,
                     prevent_default_event_handling: false,
                     ..default()
                 }),
                 ..default()
+            }).set(AudioPlugin {
+                spatial_scale: SpatialScale::new(SPATIAL_SCALE),
+                ..default()
             }),
             //WorldInspectorPlugin::new(),
             RapierPhysicsPlugin::<NoUserData>::default(),
             //RapierDebugRenderPlugin::default(),
-            EntropyPlugin::<ChaCha8Rng>::default(),
+            ReplayPlugin,
+            RandomPlugin,
             HookPlugin,
         ))
         .add_plugins((
@@ -75,14 +174,31 @@ fn main() {
             InputPlugin,
             PlayerPlugin,
             MapGeneratorPlugin,
+            NavigationPlugin,
+            FlockingPlugin,
             EnemyPlugin,
+            BossPlugin,
             UiPlugin,
             CollisionPlugin,
             EnemySpawnerPlugin,
             UpgradePlugin,
+        ))
+        // plugin tuples hold at most 15 plugins
+        .add_plugins((
             VacuumPlugin,
+            TankPlugin,
+            BlowPlugin,
+            DashPlugin,
+            PausePlugin,
+            SettingsPlugin,
+            MixerPlugin,
+            CuePlugin,
+            MusicPlugin,
             SoundPlugin,
+            SpatialSoundPlugin,
         ))
         .add_state::<GameState>()
+        .add_state::<PauseState>()
+        .configure_sets(Update, GameSet::chained())
         .run();
 }
@@ -1,47 +1,157 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, ecs::system::EntityCommands, utils::HashMap};
+use bevy_prng::ChaCha8Rng;
 use bevy_rapier3d::{prelude::*, rapier::geometry::ColliderShape};
 use bevy_scene_hook::{HookedSceneBundle, SceneHook};
+use rand::Rng;
+use rand_core::SeedableRng;
+
+use crate::{component::{DisposalPoint, GhostSpawnPoint, Player}, random::RngSeed, resource::Headless};
 
 pub struct MapGeneratorPlugin;
 
 impl Plugin for MapGeneratorPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_first);
+        app.init_resource::<LoadedChunks>()
+            .add_systems(Startup, setup_prop_assets.run_if(not(resource_exists::<Headless>())))
+            .add_systems(Update, stream_chunks);
     }
 }
 
+/// Side length of a square area chunk
+const CHUNK_SIZE: f32 = 16.0;
+/// Chunks up to this distance around the player are spawned
+const SPAWN_RADIUS: i32 = 1;
+/// Chunks further away than this are despawned, the gap avoids respawning chunks at the border
+const DESPAWN_RADIUS: i32 = 2;
+
 #[derive(Component)]
 struct NewArea;
 
-fn spawn_first(
+#[derive(Default, Resource)]
+struct LoadedChunks(HashMap<IVec2, Entity>);
+
+/// Meshes and materials of procedural props, missing if there is nothing to render them
+#[derive(Resource)]
+struct PropAssets {
+    fence_section: Handle<Mesh>,
+    column: Handle<Mesh>,
+    grave: Handle<Mesh>,
+    tombstone: Handle<Mesh>,
+    disposal: Handle<Mesh>,
+    stone: Handle<StandardMaterial>,
+    dirt: Handle<StandardMaterial>,
+    ectoplasm: Handle<StandardMaterial>,
+}
+
+fn setup_prop_assets(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut commands: Commands,
+) {
+    commands.insert_resource(PropAssets {
+        fence_section: meshes.add(Mesh::from(shape::Box::new(3.0, 2.0, 0.5))),
+        column: meshes.add(Mesh::from(shape::Box::new(0.5, 2.0, 0.5))),
+        grave: meshes.add(Mesh::from(shape::Box::new(0.8, 1.0, 1.6))),
+        tombstone: meshes.add(Mesh::from(shape::Box::new(0.5, 1.0, 0.5))),
+        disposal: meshes.add(Mesh::from(shape::Cylinder { radius: 1.0, height: 0.1, ..default() })),
+        stone: materials.add(Color::rgb(0.4, 0.4, 0.45).into()),
+        dirt: materials.add(Color::rgb(0.3, 0.2, 0.1).into()),
+        ectoplasm: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.3, 1.0, 0.4),
+            emissive: Color::rgb(0.1, 0.6, 0.2),
+            ..default()
+        }),
+    });
+}
+
+/// Inserts the collider matching the name prefix of a map node, disposal points have none
+fn insert_collider(name: &str, cmds: &mut EntityCommands) {
+    if name.starts_with("Disposal") {
+        cmds.insert(DisposalPoint);
+        return;
+    }
+    if name.starts_with("FenceSection") {
+        cmds.insert(Collider::from(ColliderShape::cuboid(1.5, 1.0, 0.25)));
+    }
+    else if name.starts_with("Column") {
+        cmds.insert(Collider::from(ColliderShape::cuboid(0.25, 1.0, 0.25)));
+    }
+    else if name.starts_with("Grave") {
+        cmds.insert(Collider::from(ColliderShape::cuboid(0.4, 0.5, 0.8)))
+        .insert(GhostSpawnPoint);
+    }
+    else if name.starts_with("Tombstone") {
+        cmds.insert(Collider::from(ColliderShape::cuboid(0.25, 0.5, 0.25)))
+        .insert(GhostSpawnPoint);
+    }
+    cmds
+    .insert(CollisionGroups::new(Group::GROUP_4, Group::GROUP_1 | Group::GROUP_6));
+}
+
+fn chunk_of(position: Vec3) -> IVec2 {
+    IVec2::new(
+        (position.x / CHUNK_SIZE).round() as i32,
+        (position.z / CHUNK_SIZE).round() as i32,
+    )
+}
+
+fn stream_chunks(
+    seed: Res<RngSeed>,
+    prop_assets: Option<Res<PropAssets>>,
     asset_server: Res<AssetServer>,
+    mut loaded_chunks: ResMut<LoadedChunks>,
+    players: Query<&Transform, With<Player>>,
     mut commands: Commands,
 ) {
+    let center = players
+        .get_single()
+        .map(|player| chunk_of(player.translation))
+        .unwrap_or(IVec2::ZERO);
+
+    loaded_chunks.0.retain(|chunk, entity| {
+        let distance = (*chunk - center).abs().max_element();
+        if distance > DESPAWN_RADIUS {
+            info!("despawning area {}", chunk);
+            commands.entity(*entity).despawn_recursive();
+            return false;
+        }
+        true
+    });
+
+    for x in -SPAWN_RADIUS..=SPAWN_RADIUS {
+        for z in -SPAWN_RADIUS..=SPAWN_RADIUS {
+            let chunk = center + IVec2::new(x, z);
+            if loaded_chunks.0.contains_key(&chunk) {
+                continue;
+            }
+            let entity = if chunk == IVec2::ZERO {
+                spawn_first(&asset_server, &mut commands)
+            }
+            else {
+                spawn_area(chunk, seed.0, prop_assets.as_deref(), &mut commands)
+            };
+            loaded_chunks.0.insert(chunk, entity);
+        }
+    }
+}
+
+/// The authored area has no disposal point of its own, so there is one next to the start
+const FIRST_DISPOSAL: Vec3 = Vec3::new(0.0, 0.0, 5.0);
+
+/// The area the player starts in is authored by hand
+fn spawn_first(
+    asset_server: &AssetServer,
+    commands: &mut Commands,
+) -> Entity {
     commands.spawn(HookedSceneBundle {
         scene: SceneBundle {
         scene: asset_server.load("areas/default.glb#Scene0"),
         ..default()
         },
         hook: SceneHook::new(|entity, cmds| {
-            match entity.get::<Name>().map(|t| t.as_str()) {
-                Some(string) => {
-                    if string.starts_with("FenceSection") {
-                        cmds.insert(Collider::from(ColliderShape::cuboid(1.5, 1.0, 0.25)));
-                    }
-                    else if string.starts_with("Column") {
-                        cmds.insert(Collider::from(ColliderShape::cuboid(0.25, 1.0, 0.25)));
-                    }
-                    else if string.starts_with("Grave") {
-                        cmds.insert(Collider::from(ColliderShape::cuboid(0.4, 0.5, 0.8)));
-                    }
-                    else if string.starts_with("Tombstone") {
-                        cmds.insert(Collider::from(ColliderShape::cuboid(0.25, 0.5, 0.25)));
-                    }
-                    cmds
-                    .insert(CollisionGroups::new(Group::GROUP_4, Group::GROUP_1))
-                },
-                _ => cmds,
-            };
+            if let Some(name) = entity.get::<Name>() {
+                insert_collider(name.as_str(), cmds);
+            }
         }),
     })
     .insert(Name::from("Area-0-0"))
@@ -50,5 +160,92 @@ fn spawn_first(
         .insert(TransformBundle::from(Transform::from_xyz(0.0, 1.0, 10.0)))
         .insert(Name::from("LowerBarrier"))
         .insert(CollisionGroups::new(Group::GROUP_4, Group::GROUP_1));
-    });
+        parent.spawn(SpatialBundle::from_transform(Transform::from_translation(FIRST_DISPOSAL)))
+        .insert(Name::from("Disposal-0"))
+        .insert(DisposalPoint);
+    })
+    .id()
+}
+
+const GRAVE_ROWS: i32 = 3;
+const GRAVE_COLUMNS: i32 = 4;
+const GRAVE_CHANCE: f64 = 0.6;
+const FENCE_CHANCE: f64 = 0.3;
+const DISPOSAL_CHANCE: f64 = 0.25;
+
+/// Generates a graveyard area from the world seed and its chunk coordinates,
+/// so an area looks the same no matter when or how often it is spawned
+fn spawn_area(
+    chunk: IVec2,
+    seed: u64,
+    prop_assets: Option<&PropAssets>,
+    commands: &mut Commands,
+) -> Entity {
+    let chunk_seed = seed
+        ^ (chunk.x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (chunk.y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    let mut rng = ChaCha8Rng::seed_from_u64(chunk_seed);
+    let origin = Vec3::new(chunk.x as f32 * CHUNK_SIZE, 0.0, chunk.y as f32 * CHUNK_SIZE);
+
+    let mut props = Vec::new();
+
+    // graves are laid out in rows with a tombstone at their head
+    let spacing = Vec2::new(CHUNK_SIZE / GRAVE_COLUMNS as f32, CHUNK_SIZE / GRAVE_ROWS as f32);
+    for row in 0..GRAVE_ROWS {
+        for column in 0..GRAVE_COLUMNS {
+            if !rng.gen_bool(GRAVE_CHANCE) {
+                continue;
+            }
+            let x = -CHUNK_SIZE / 2.0 + spacing.x * (column as f32 + 0.5) + rng.gen_range(-0.5..0.5);
+            let z = -CHUNK_SIZE / 2.0 + spacing.y * (row as f32 + 0.5) + rng.gen_range(-0.5..0.5);
+            props.push(("Grave", Vec3::new(x, 0.0, z), 0.0));
+            props.push(("Tombstone", Vec3::new(x, 0.5, z - 1.05), rng.gen_range(-0.2..0.2)));
+        }
+    }
+
+    for _ in 0..rng.gen_range(0..=2) {
+        let x = rng.gen_range(-CHUNK_SIZE / 2.0..CHUNK_SIZE / 2.0);
+        let z = rng.gen_range(-CHUNK_SIZE / 2.0..CHUNK_SIZE / 2.0);
+        props.push(("Column", Vec3::new(x, 1.0, z), 0.0));
+    }
+
+    if rng.gen_bool(DISPOSAL_CHANCE) {
+        let x = rng.gen_range(-CHUNK_SIZE / 4.0..CHUNK_SIZE / 4.0);
+        let z = rng.gen_range(-CHUNK_SIZE / 4.0..CHUNK_SIZE / 4.0);
+        props.push(("Disposal", Vec3::new(x, 0.0, z), 0.0));
+    }
+
+    // broken fences along the northern border of the area
+    let sections = (CHUNK_SIZE / 3.0) as i32;
+    for section in 0..sections {
+        if rng.gen_bool(FENCE_CHANCE) {
+            let x = -CHUNK_SIZE / 2.0 + 3.0 * (section as f32 + 0.5);
+            props.push(("FenceSection", Vec3::new(x, 1.0, -CHUNK_SIZE / 2.0), 0.0));
+        }
+    }
+
+    commands.spawn(SpatialBundle::from_transform(Transform::from_translation(origin)))
+    .insert(Name::from(format!("Area-{}-{}", chunk.x, chunk.y)))
+    .insert(NewArea)
+    .with_children(|parent| {
+        for (index, (kind, position, rotation)) in props.into_iter().enumerate() {
+            let transform = Transform::from_translation(position).with_rotation(Quat::from_rotation_y(rotation));
+            let name = format!("{}-{}", kind, index);
+            let mut cmds = parent.spawn(SpatialBundle::from_transform(transform));
+            insert_collider(&name, &mut cmds);
+            cmds.insert(Name::from(name));
+
+            if let Some(assets) = prop_assets {
+                let (mesh, material) = match kind {
+                    "FenceSection" => (&assets.fence_section, &assets.stone),
+                    "Column" => (&assets.column, &assets.stone),
+                    "Grave" => (&assets.grave, &assets.dirt),
+                    "Disposal" => (&assets.disposal, &assets.ectoplasm),
+                    _ => (&assets.tombstone, &assets.stone),
+                };
+                cmds.insert((mesh.clone(), material.clone()));
+            }
+        }
+    })
+    .id()
 }
@@ -0,0 +1,133 @@
+use bevy::{prelude::*, audio::{SpatialAudioSink, Volume, VolumeLevel}};
+
+use crate::settings::Settings;
+
+/// Mixes every sound through a bus, changing the volume of a bus applies to sounds that are already playing
+pub struct MixerPlugin;
+
+impl Plugin for MixerPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Mixer::default())
+            .add_systems(Update, (
+                sync_settings.run_if(resource_changed::<Settings>()),
+                apply_mixer,
+            ).chain());
+    }
+}
+
+/// Music volume while the music is ducked
+const DUCK_LEVEL: f32 = 0.3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bus {
+    Music,
+    Sfx,
+    Ui,
+}
+
+/// Volumes of the buses, the master volume applies to all of them
+#[derive(Debug, Resource)]
+pub struct Mixer {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub ui: f32,
+    /// Turns the music down, for example while the game is paused
+    pub ducked: bool,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 1.0,
+            sfx: 1.0,
+            ui: 1.0,
+            ducked: false,
+        }
+    }
+}
+
+impl Mixer {
+    pub fn bus(&self, bus: Bus) -> f32 {
+        let volume = match bus {
+            Bus::Music if self.ducked => self.music * DUCK_LEVEL,
+            Bus::Music => self.music,
+            Bus::Sfx => self.sfx,
+            Bus::Ui => self.ui,
+        };
+        self.master * volume
+    }
+
+    pub fn level(&self, channel: &Channel) -> f32 {
+        self.bus(channel.bus) * channel.gain * channel.fade
+    }
+
+    /// A sound that plays on the bus of the channel, spawn it to play it
+    pub fn audio(&self, source: Handle<AudioSource>, channel: Channel, settings: PlaybackSettings) -> (AudioBundle, Channel) {
+        (
+            AudioBundle {
+                source,
+                settings: PlaybackSettings {
+                    volume: Volume::Relative(VolumeLevel::new(self.level(&channel))),
+                    ..settings
+                },
+            },
+            channel,
+        )
+    }
+}
+
+/// How a sound is mixed, its volume is the one of its bus times the gain and the fade
+#[derive(Clone, Component, Copy, Debug)]
+pub struct Channel {
+    pub bus: Bus,
+    /// The volume of the sound itself
+    pub gain: f32,
+    /// Changed by crossfades and fade outs, from 0 to 1
+    pub fade: f32,
+}
+
+impl Channel {
+    pub fn new(bus: Bus, gain: f32) -> Self {
+        Self {
+            bus,
+            gain,
+            fade: 1.0,
+        }
+    }
+
+    pub fn with_fade(mut self, fade: f32) -> Self {
+        self.fade = fade;
+        self
+    }
+}
+
+fn sync_settings(
+    settings: Res<Settings>,
+    mut mixer: ResMut<Mixer>,
+) {
+    mixer.master = settings.master_volume;
+    mixer.music = settings.music_volume;
+    mixer.sfx = settings.sfx_volume;
+    mixer.ui = settings.ui_volume;
+}
+
+/// Keeps the volume of every playing sound in line with its bus and channel
+fn apply_mixer(
+    mixer: Res<Mixer>,
+    sinks: Query<(Ref<AudioSink>, Ref<Channel>)>,
+    spatial_sinks: Query<(Ref<SpatialAudioSink>, Ref<Channel>)>,
+) {
+    for (sink, channel) in &sinks {
+        // sinks are added once the sound has started, which is when their volume has to be set for the first time
+        if mixer.is_changed() || channel.is_changed() || sink.is_added() {
+            sink.set_volume(mixer.level(&channel));
+        }
+    }
+    for (sink, channel) in &spatial_sinks {
+        if mixer.is_changed() || channel.is_changed() || sink.is_added() {
+            sink.set_volume(mixer.level(&channel));
+        }
+    }
+}
@@ -0,0 +1,506 @@
+use std::collections::HashMap;
+
+use bevy::{prelude::*, asset::{AssetLoader, AsyncReadExt, LoadContext, LoadState, io::Reader}, time::Real, utils::BoxedFuture};
+use serde::Deserialize;
+
+use crate::{boss::Boss, component::Ghost, enemy_spawner::GhostSpawnConfig, events::WaveEnd, mixer::{Bus, Channel, Mixer}, resource::Stats, GameState, playing};
+
+/// Plays the music as synchronized stems that fade in and out with the danger level.
+/// The stems and stingers are defined in `*.music.ron` files.
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<MusicDefinition>()
+            .register_asset_loader(MusicDefinitionLoader)
+            .insert_resource(DangerLevel::new())
+            .init_resource::<MusicClock>()
+            .init_resource::<StingerQueue>()
+            .add_systems(Startup, load_music)
+            .add_systems(OnEnter(GameState::Menu), reset_danger_level)
+            .add_systems(OnExit(GameState::Menu), stop_stems)
+            .add_systems(OnEnter(GameState::Game), reset_danger_level)
+            .add_systems(OnExit(GameState::Game), clear_stingers)
+            .add_systems(Update, (
+                reload_music,
+                start_stems.run_if(in_state(GameState::Menu).or_else(in_state(GameState::Game))),
+                update_stems,
+                play_stingers,
+            ).chain())
+            .add_systems(Update, (
+                update_danger_level,
+                // the clock of the stems has to be set before stingers are aligned to it
+                queue_stingers.after(start_stems),
+            ).run_if(playing));
+    }
+}
+
+pub const MUSIC_PATH: &str = "default.music.ron";
+
+#[derive(Resource)]
+pub struct Music(pub Handle<MusicDefinition>);
+
+fn load_music(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    commands.insert_resource(Music(asset_server.load(MUSIC_PATH)));
+}
+
+/// How the volume of a stem follows the progress of its fade
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub enum FadeCurve {
+    #[default]
+    Linear,
+    /// Starts quiet and gets loud towards the end
+    EaseIn,
+    /// Gets loud quickly and levels off
+    EaseOut,
+    /// Keeps the loudness of a crossfade between two stems even
+    EqualPower,
+}
+
+impl FadeCurve {
+    /// Maps the progress of a fade to a volume, both from 0 to 1
+    pub fn apply(&self, progress: f32) -> f32 {
+        let x = progress.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => x,
+            FadeCurve::EaseIn => x * x,
+            FadeCurve::EaseOut => 1.0 - (1.0 - x).powi(2),
+            FadeCurve::EqualPower => (x * std::f32::consts::FRAC_PI_2).sin(),
+        }
+    }
+}
+
+/// A stem plays while the danger level is between `from` and `to`
+#[derive(Clone, Debug, Deserialize)]
+pub struct StemDefinition {
+    pub path: String,
+    pub from: f32,
+    pub to: f32,
+    #[serde(default = "full_gain")]
+    pub gain: f32,
+    /// Length of a fade in beats
+    pub fade_beats: f32,
+    #[serde(default)]
+    pub curve: FadeCurve,
+}
+
+fn full_gain() -> f32 {
+    1.0
+}
+
+impl StemDefinition {
+    /// A playing stem keeps playing until the danger level is further than the hysteresis outside of its range
+    fn wanted(&self, danger: f32, playing: bool, hysteresis: f32) -> bool {
+        let margin = if playing { hysteresis } else { 0.0 };
+        danger >= self.from - margin && danger < self.to + margin
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
+pub enum Stinger {
+    WaveStart,
+    WaveEnd,
+    Boss,
+    LowHealth,
+}
+
+#[derive(Asset, Debug, Deserialize, TypePath)]
+pub struct MusicDefinition {
+    /// Tempo of the stems, transitions and stingers are aligned to its beats
+    pub bpm: f32,
+    /// Stems only start or stop fading on a multiple of this many beats
+    pub transition_beats: f32,
+    pub stinger_beats: f32,
+    /// How far the danger level has to leave the range of a playing stem to stop it
+    pub hysteresis: f32,
+    /// Normalized health below which the low health stinger plays
+    pub low_health: f32,
+    #[serde(default = "full_gain")]
+    pub stinger_gain: f32,
+    pub stems: Vec<StemDefinition>,
+    /// Stingers without a sound are left out
+    #[serde(default)]
+    pub stingers: HashMap<Stinger, String>,
+}
+
+impl MusicDefinition {
+    fn beat_length(&self) -> f64 {
+        60.0 / self.bpm.max(1.0) as f64
+    }
+
+    /// The first multiple of `beats` beats since the start of the stems that is not before `now`
+    fn next_beat(&self, start: f64, now: f64, beats: f32) -> f64 {
+        let length = self.beat_length() * beats as f64;
+        if length <= 0.0 {
+            return now;
+        }
+        start + ((now - start) / length).ceil() * length
+    }
+}
+
+#[derive(Default)]
+struct MusicDefinitionLoader;
+
+impl AssetLoader for MusicDefinitionLoader {
+    type Asset = MusicDefinition;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(ron::de::from_bytes(&bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["music.ron"]
+    }
+}
+
+#[derive(Resource)]
+struct DangerLevel {
+    remaining_health: f32,
+    ghosts_on_screen: f32,
+    health_weight: f32,
+    ghost_weight: f32,
+}
+
+impl DangerLevel {
+    pub fn new() -> Self {
+        Self {
+            remaining_health: 1.0,
+            ghosts_on_screen: 0.0,
+            health_weight: 2.0,
+            ghost_weight: 1.0,
+        }
+    }
+
+    pub fn update(&mut self, remaining_health: f32, ghosts_on_screen: f32) {
+        self.remaining_health = remaining_health;
+        self.ghosts_on_screen = ghosts_on_screen;
+    }
+
+    /// Returns the normalized danger level with weights applied
+    pub fn danger_level(&self) -> f32 {
+        let missing_health = 1.0 - self.remaining_health;
+        (missing_health * self.health_weight + self.ghosts_on_screen * self.ghost_weight) / (self.health_weight + self.ghost_weight)
+    }
+}
+
+fn reset_danger_level(
+    mut danger_level: ResMut<DangerLevel>,
+) {
+    *danger_level = DangerLevel::new();
+}
+
+fn update_danger_level(
+    stats: Res<Stats>,
+    wave_config: Res<GhostSpawnConfig>,
+    mut danger_level: ResMut<DangerLevel>,
+    ghosts: Query<&Ghost>,
+) {
+    let ghosts_on_screen = ghosts.iter().collect::<Vec<&Ghost>>().len();
+    danger_level.update(stats.normalized_health(), ghosts_on_screen as f32 / wave_config.wave_size() as f32);
+}
+
+/// When the stems started, in real time because the music keeps playing while the game is paused
+#[derive(Default, Resource)]
+struct MusicClock {
+    start: Option<f64>,
+}
+
+#[derive(Component)]
+struct Stem {
+    index: usize,
+    active: bool,
+    /// Progress of the fade, 1 is fully faded in
+    progress: f32,
+    /// The beat on which the stem starts or stops fading
+    switch_at: Option<f64>,
+}
+
+/// Starts all stems in the same frame once they and the stingers are loaded, so they play in sync
+#[allow(clippy::too_many_arguments)]
+fn start_stems(
+    time: Res<Time<Real>>,
+    asset_server: Res<AssetServer>,
+    mixer: Res<Mixer>,
+    music: Res<Music>,
+    definitions: Res<Assets<MusicDefinition>>,
+    danger_level: Res<DangerLevel>,
+    mut clock: ResMut<MusicClock>,
+    mut sources: Local<Vec<Handle<AudioSource>>>,
+    mut reported_failures: Local<bool>,
+    stems: Query<(), With<Stem>>,
+    mut commands: Commands,
+) {
+    if !stems.is_empty() {
+        return;
+    }
+    let Some(definition) = definitions.get(&music.0) else {
+        return;
+    };
+
+    // the handles are kept so the sounds stay loaded between games
+    *sources = definition.stems.iter().map(|stem| &stem.path)
+        .chain(definition.stingers.values())
+        .map(|path| asset_server.load(path))
+        .collect();
+    let loading = sources.iter().any(|source| {
+        !matches!(asset_server.get_load_state(source), Some(LoadState::Loaded) | Some(LoadState::Failed))
+    });
+    if loading {
+        return;
+    }
+
+    // a stem that could not be loaded stays silent instead of holding up the others
+    let loaded: Vec<_> = definition.stems.iter().zip(sources.iter()).enumerate()
+        .filter(|(_, (stem, source))| {
+            let failed = asset_server.get_load_state(*source) == Some(LoadState::Failed);
+            if failed && !*reported_failures {
+                warn!("could not load music stem {}", stem.path);
+            }
+            !failed
+        })
+        .collect();
+    *reported_failures = true;
+    if loaded.is_empty() {
+        return;
+    }
+
+    info!("starting {} of {} music stems", loaded.len(), definition.stems.len());
+    clock.start = Some(time.elapsed_seconds_f64());
+    let danger = danger_level.danger_level();
+    for (index, (stem, source)) in loaded {
+        let active = stem.wanted(danger, false, definition.hysteresis);
+        let progress = if active { 1.0 } else { 0.0 };
+        commands.spawn(mixer.audio(source.clone(), Channel::new(Bus::Music, stem.gain).with_fade(progress), PlaybackSettings::LOOP))
+            .insert(Stem {
+                index,
+                active,
+                progress,
+                switch_at: None,
+            });
+    }
+}
+
+fn stop_stems(
+    mut clock: ResMut<MusicClock>,
+    query: Query<Entity, With<Stem>>,
+    mut commands: Commands,
+) {
+    clock.start = None;
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Restarts the stems whenever the music definition is changed on disk
+fn reload_music(
+    music: Res<Music>,
+    mut asset_events: EventReader<AssetEvent<MusicDefinition>>,
+    stems: Query<Entity, With<Stem>>,
+    mut commands: Commands,
+) {
+    for asset_event in asset_events.read() {
+        if asset_event.is_modified(&music.0) {
+            info!("reloading the music");
+            for entity in &stems {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Fades every stem towards whether the danger level is inside of its range, fades start on the beat
+fn update_stems(
+    time: Res<Time<Real>>,
+    music: Res<Music>,
+    definitions: Res<Assets<MusicDefinition>>,
+    danger_level: Res<DangerLevel>,
+    clock: Res<MusicClock>,
+    mut stems: Query<(&mut Stem, &mut Channel)>,
+) {
+    let (Some(definition), Some(start)) = (definitions.get(&music.0), clock.start) else {
+        return;
+    };
+    let now = time.elapsed_seconds_f64();
+    let danger = danger_level.danger_level();
+
+    for (mut stem, mut channel) in &mut stems {
+        let Some(stem_definition) = definition.stems.get(stem.index) else {
+            continue;
+        };
+
+        let wanted = stem_definition.wanted(danger, stem.active, definition.hysteresis);
+        if wanted == stem.active {
+            stem.switch_at = None;
+        }
+        else {
+            let switch_at = *stem.switch_at.get_or_insert_with(|| definition.next_beat(start, now, definition.transition_beats));
+            if now >= switch_at {
+                stem.active = wanted;
+                stem.switch_at = None;
+            }
+        }
+
+        let target = if stem.active { 1.0 } else { 0.0 };
+        if stem.progress == target {
+            continue;
+        }
+        let fade_time = stem_definition.fade_beats * definition.beat_length() as f32;
+        let step = if fade_time > 0.0 { time.delta_seconds() / fade_time } else { 1.0 };
+        stem.progress = if stem.active { (stem.progress + step).min(1.0) } else { (stem.progress - step).max(0.0) };
+        channel.fade = stem_definition.curve.apply(stem.progress);
+    }
+}
+
+/// Stingers waiting for their beat
+#[derive(Default, Resource)]
+struct StingerQueue(Vec<(f64, Stinger)>);
+
+#[allow(clippy::too_many_arguments)]
+fn queue_stingers(
+    time: Res<Time<Real>>,
+    music: Res<Music>,
+    definitions: Res<Assets<MusicDefinition>>,
+    clock: Res<MusicClock>,
+    stats: Res<Stats>,
+    wave_config: Res<GhostSpawnConfig>,
+    mut wave_end_events: EventReader<WaveEnd>,
+    bosses: Query<(), Added<Boss>>,
+    mut last_wave: Local<u32>,
+    mut low_health: Local<bool>,
+    mut queue: ResMut<StingerQueue>,
+) {
+    let mut stingers = Vec::new();
+    // a new game inserts a new config, so the first wave starts again after a restart
+    if wave_config.is_added() || wave_config.current_wave() != *last_wave {
+        *last_wave = wave_config.current_wave();
+        stingers.push(Stinger::WaveStart);
+    }
+    if wave_end_events.read().count() > 0 {
+        stingers.push(Stinger::WaveEnd);
+    }
+    if !bosses.is_empty() {
+        stingers.push(Stinger::Boss);
+    }
+
+    let (Some(definition), Some(start)) = (definitions.get(&music.0), clock.start) else {
+        return;
+    };
+    let is_low = stats.normalized_health() <= definition.low_health;
+    if is_low && !*low_health {
+        stingers.push(Stinger::LowHealth);
+    }
+    *low_health = is_low;
+
+    let at = definition.next_beat(start, time.elapsed_seconds_f64(), definition.stinger_beats);
+    queue.0.extend(stingers.into_iter().map(|stinger| (at, stinger)));
+}
+
+fn play_stingers(
+    time: Res<Time<Real>>,
+    asset_server: Res<AssetServer>,
+    mixer: Res<Mixer>,
+    music: Res<Music>,
+    definitions: Res<Assets<MusicDefinition>>,
+    mut queue: ResMut<StingerQueue>,
+    mut commands: Commands,
+) {
+    let Some(definition) = definitions.get(&music.0) else {
+        return;
+    };
+    let now = time.elapsed_seconds_f64();
+    queue.0.retain(|(at, stinger)| {
+        if *at > now {
+            return true;
+        }
+        if let Some(path) = definition.stingers.get(stinger) {
+            info!("playing the {:?} stinger", stinger);
+            commands.spawn(mixer.audio(asset_server.load(path), Channel::new(Bus::Music, definition.stinger_gain), PlaybackSettings::DESPAWN));
+        }
+        false
+    });
+}
+
+fn clear_stingers(
+    mut queue: ResMut<StingerQueue>,
+) {
+    queue.0.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(bpm: f32) -> MusicDefinition {
+        MusicDefinition {
+            bpm,
+            transition_beats: 4.0,
+            stinger_beats: 1.0,
+            hysteresis: 0.0,
+            low_health: 0.25,
+            stinger_gain: 1.0,
+            stems: Vec::new(),
+            stingers: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn fade_curves_go_from_silent_to_full() {
+        for curve in [FadeCurve::Linear, FadeCurve::EaseIn, FadeCurve::EaseOut, FadeCurve::EqualPower] {
+            assert_eq!(curve.apply(0.0), 0.0, "{:?}", curve);
+            assert!((curve.apply(1.0) - 1.0).abs() < 1e-6, "{:?}", curve);
+            // progress outside of the fade is clamped
+            assert_eq!(curve.apply(-1.0), curve.apply(0.0), "{:?}", curve);
+            assert_eq!(curve.apply(2.0), curve.apply(1.0), "{:?}", curve);
+        }
+    }
+
+    #[test]
+    fn fade_curves_have_their_shape() {
+        assert_eq!(FadeCurve::Linear.apply(0.5), 0.5);
+        assert_eq!(FadeCurve::EaseIn.apply(0.5), 0.25);
+        assert_eq!(FadeCurve::EaseOut.apply(0.5), 0.75);
+        // two stems crossfading with equal power add up to the same loudness
+        let (rising, falling) = (FadeCurve::EqualPower.apply(0.3), FadeCurve::EqualPower.apply(0.7));
+        assert!((rising * rising + falling * falling - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn next_beat_is_aligned_to_the_start() {
+        // a beat is half a second at 120 bpm
+        let definition = definition(120.0);
+        assert_eq!(definition.next_beat(10.0, 10.0, 1.0), 10.0);
+        assert_eq!(definition.next_beat(10.0, 10.1, 1.0), 10.5);
+        assert_eq!(definition.next_beat(10.0, 10.5, 1.0), 10.5);
+        assert_eq!(definition.next_beat(10.0, 10.6, 4.0), 12.0);
+        assert_eq!(definition.next_beat(10.0, 12.1, 4.0), 14.0);
+    }
+
+    #[test]
+    fn next_beat_without_length_is_now() {
+        assert_eq!(definition(120.0).next_beat(10.0, 11.3, 0.0), 11.3);
+    }
+
+    #[test]
+    fn default_music_points_at_existing_sounds() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let content = std::fs::read_to_string(path.join(MUSIC_PATH)).unwrap();
+        let definition: MusicDefinition = ron::from_str(&content).unwrap();
+        for sound in definition.stems.iter().map(|stem| &stem.path).chain(definition.stingers.values()) {
+            assert!(path.join(sound).exists(), "{} is missing", sound);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{prelude::*, transform::TransformSystem, utils::{HashMap, HashSet}};
+use bevy_rapier3d::prelude::*;
+
+use crate::{component::Player, GameSet};
+
+/// Keeps a grid of cells blocked by walls and a flow field leading around them to the player
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavGrid>()
+            .init_resource::<FlowField>()
+            .add_systems(Update, (mark_dirty, update_flow_field).chain().in_set(GameSet::Navigation))
+            .add_systems(PostUpdate, rebuild_grid.after(TransformSystem::TransformPropagate));
+    }
+}
+
+const CELL_SIZE: f32 = 0.5;
+/// The flow field is only computed this many cells around the player
+const FLOW_RADIUS: i32 = 48;
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+const NEIGHBOURS: [IVec2; 8] = [
+    IVec2::new(1, 0),
+    IVec2::new(-1, 0),
+    IVec2::new(0, 1),
+    IVec2::new(0, -1),
+    IVec2::new(1, 1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, -1),
+];
+
+fn cell_of(position: Vec3) -> IVec2 {
+    IVec2::new(
+        (position.x / CELL_SIZE).floor() as i32,
+        (position.z / CELL_SIZE).floor() as i32,
+    )
+}
+
+fn center_of(cell: IVec2) -> Vec3 {
+    Vec3::new((cell.x as f32 + 0.5) * CELL_SIZE, 0.0, (cell.y as f32 + 0.5) * CELL_SIZE)
+}
+
+#[derive(Default, Resource)]
+pub struct NavGrid {
+    blocked: HashSet<IVec2>,
+    walls: HashSet<Entity>,
+    dirty: bool,
+    /// Increased on every rebuild, so the flow field knows when it is outdated
+    version: u32,
+}
+
+impl NavGrid {
+    pub fn is_blocked(&self, cell: IVec2) -> bool {
+        self.blocked.contains(&cell)
+    }
+
+    /// Moving diagonally is only allowed if it doesn't cut a corner
+    fn can_move(&self, from: IVec2, offset: IVec2) -> bool {
+        let to = from + offset;
+        !self.is_blocked(to)
+            && !self.is_blocked(IVec2::new(from.x + offset.x, from.y))
+            && !self.is_blocked(IVec2::new(from.x, from.y + offset.y))
+    }
+}
+
+#[derive(Default, Resource)]
+pub struct FlowField {
+    target: Option<IVec2>,
+    version: u32,
+    costs: HashMap<IVec2, u32>,
+}
+
+impl FlowField {
+    /// Returns the direction leading around walls towards the player,
+    /// or `None` if the position is outside of the field or inside a wall
+    pub fn direction(&self, grid: &NavGrid, position: Vec3) -> Option<Vec3> {
+        let cell = cell_of(position);
+        let cost = *self.costs.get(&cell)?;
+        let next = NEIGHBOURS
+            .iter()
+            .filter(|offset| grid.can_move(cell, **offset))
+            .filter_map(|offset| self.costs.get(&(cell + *offset)).map(|cost| (cell + *offset, *cost)))
+            .min_by_key(|(_, cost)| *cost)
+            .filter(|(_, next_cost)| *next_cost < cost)?;
+        let mut direction = center_of(next.0) - position;
+        direction.y = 0.0;
+        Some(direction.normalize_or_zero())
+    }
+}
+
+fn is_wall(groups: &CollisionGroups) -> bool {
+    groups.memberships.contains(Group::GROUP_4)
+}
+
+fn mark_dirty(
+    mut grid: ResMut<NavGrid>,
+    added: Query<&CollisionGroups, Added<Collider>>,
+    mut removed: RemovedComponents<Collider>,
+) {
+    let wall_added = added.iter().any(is_wall);
+    let wall_removed = removed.read().any(|entity| grid.walls.contains(&entity));
+    if wall_added || wall_removed {
+        grid.dirty = true;
+    }
+}
+
+/// Marks every cell overlapped by the bounding box of a cuboid wall as blocked
+fn rebuild_grid(
+    mut grid: ResMut<NavGrid>,
+    walls: Query<(Entity, &Collider, &GlobalTransform, &CollisionGroups)>,
+) {
+    if !grid.dirty {
+        return;
+    }
+
+    let mut blocked = HashSet::new();
+    let mut wall_entities = HashSet::new();
+    for (entity, collider, transform, groups) in &walls {
+        if !is_wall(groups) {
+            continue;
+        }
+        let Some(cuboid) = collider.as_cuboid() else {
+            continue;
+        };
+        wall_entities.insert(entity);
+
+        let half = cuboid.half_extents();
+        let mut min = Vec3::splat(f32::MAX);
+        let mut max = Vec3::splat(f32::MIN);
+        for x in [-half.x, half.x] {
+            for y in [-half.y, half.y] {
+                for z in [-half.z, half.z] {
+                    let corner = transform.transform_point(Vec3::new(x, y, z));
+                    min = min.min(corner);
+                    max = max.max(corner);
+                }
+            }
+        }
+
+        let (min, max) = (cell_of(min), cell_of(max));
+        for x in min.x..=max.x {
+            for z in min.y..=max.y {
+                blocked.insert(IVec2::new(x, z));
+            }
+        }
+    }
+
+    info!("rebuilt navigation grid with {} blocked cells", blocked.len());
+    grid.blocked = blocked;
+    grid.walls = wall_entities;
+    grid.dirty = false;
+    grid.version += 1;
+}
+
+/// Runs dijkstra from the player outwards whenever the player changes cells or the walls change
+fn update_flow_field(
+    grid: Res<NavGrid>,
+    mut flow_field: ResMut<FlowField>,
+    players: Query<&Transform, With<Player>>,
+) {
+    let Ok(player) = players.get_single() else {
+        return;
+    };
+    let target = cell_of(player.translation);
+    if flow_field.target == Some(target) && flow_field.version == grid.version {
+        return;
+    }
+
+    let mut costs = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    costs.insert(target, 0);
+    queue.push(Reverse((0, target.x, target.y)));
+
+    while let Some(Reverse((cost, x, y))) = queue.pop() {
+        let cell = IVec2::new(x, y);
+        if costs.get(&cell).is_some_and(|known| *known < cost) {
+            continue;
+        }
+        for offset in NEIGHBOURS {
+            let next = cell + offset;
+            if (next - target).abs().max_element() > FLOW_RADIUS || !grid.can_move(cell, offset) {
+                continue;
+            }
+            let step = if offset.x != 0 && offset.y != 0 { DIAGONAL_COST } else { STRAIGHT_COST };
+            let next_cost = cost + step;
+            if costs.get(&next).is_none_or(|known| next_cost < *known) {
+                costs.insert(next, next_cost);
+                queue.push(Reverse((next_cost, next.x, next.y)));
+            }
+        }
+    }
+
+    flow_field.target = Some(target);
+    flow_field.version = grid.version;
+    flow_field.costs = costs;
+}
@@ -0,0 +1,110 @@
+use bevy::{prelude::*, time::Virtual};
+use bevy_rapier3d::prelude::*;
+
+use crate::{events::VacuumEvent, resource::{InputValues, Headless}, GameState, PauseState};
+
+/// Pauses a running game with Escape or the start button of a gamepad.
+/// A paused game freezes physics and the time gameplay timers run on.
+/// Also restarts a game, which leaves the pause menu and the game over screen alike.
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, toggle_pause.run_if(in_state(GameState::Game).and_then(not(resource_exists::<Headless>()))))
+            .add_systems(OnEnter(GameState::Restarting), restart_game)
+            .add_systems(OnEnter(PauseState::Paused), freeze)
+            .add_systems(OnExit(PauseState::Paused), (unfreeze, release_vacuum));
+    }
+}
+
+fn toggle_pause(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    pause_state: Res<State<PauseState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+) {
+    let pressed = keys.just_pressed(KeyCode::Escape)
+        || gamepads.iter().any(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::Start)));
+    if !pressed {
+        return;
+    }
+
+    match pause_state.get() {
+        PauseState::Running => next_pause_state.set(PauseState::Paused),
+        PauseState::Paused => next_pause_state.set(PauseState::Running),
+    }
+}
+
+/// Setting the game state to the one it is already in does nothing, so restarts go through `Restarting`
+fn restart_game(
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    game_state.set(GameState::Game);
+}
+
+fn freeze(
+    mut time: ResMut<Time<Virtual>>,
+    mut rapier_configuration: ResMut<RapierConfiguration>,
+) {
+    info!("pausing the game");
+    time.pause();
+    rapier_configuration.physics_pipeline_active = false;
+}
+
+fn unfreeze(
+    mut time: ResMut<Time<Virtual>>,
+    mut rapier_configuration: ResMut<RapierConfiguration>,
+) {
+    info!("resuming the game");
+    time.unpause();
+    rapier_configuration.physics_pipeline_active = true;
+}
+
+/// Mouse input isn't read while paused, so a button held when pausing counts as released afterwards
+fn release_vacuum(
+    mut input_values: ResMut<InputValues>,
+    mut vacuum_events: EventWriter<VacuumEvent>,
+) {
+    if input_values.mouse_pressed {
+        input_values.mouse_pressed = false;
+        vacuum_events.send(VacuumEvent::Stop);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        component::{Ghost, Player},
+        enemy_spawner::GhostSpawnConfig,
+        headless::{HeadlessConfig, HeadlessPlugin},
+        random::RngSeed,
+        resource::Stats,
+    };
+
+    #[test]
+    fn restart_sets_up_a_new_game() {
+        let mut app = App::new();
+        app.insert_resource(RngSeed(7))
+            .add_plugins(HeadlessPlugin(HeadlessConfig { max_waves: u32::MAX, max_frames: u32::MAX, ..default() }));
+        app.finish();
+        app.cleanup();
+        // the spawn config is inserted once the waves are loaded
+        while app.world.get_resource::<GhostSpawnConfig>().is_none_or(|config| config.current_wave() < 2) {
+            app.update();
+        }
+        assert!(app.world.resource::<Stats>().sucked_ghosts > 0);
+
+        app.world.resource_mut::<NextState<GameState>>().set(GameState::Restarting);
+        app.update();
+        assert_eq!(*app.world.resource::<State<GameState>>().get(), GameState::Restarting);
+        app.update();
+        assert_eq!(*app.world.resource::<State<GameState>>().get(), GameState::Game);
+
+        assert_eq!(app.world.resource::<Stats>().sucked_ghosts, 0);
+        assert_eq!(app.world.resource::<GhostSpawnConfig>().current_wave(), 1);
+        assert_eq!(app.world.query_filtered::<(), With<Player>>().iter(&app.world).count(), 1);
+        assert_eq!(app.world.query_filtered::<(), With<Ghost>>().iter(&app.world).count(), 0);
+    }
+}
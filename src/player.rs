@@ -1,8 +1,9 @@
 use bevy::prelude::*;
-use bevy_rapier3d::{prelude::*, rapier::geometry::ColliderShape};
+use rand::thread_rng;
+use bevy_rapier3d::prelude::*;
 use bevy_scene_hook::{SceneHook, HookedSceneBundle};
 
-use crate::{resource::{InputValues, Stats}, component::{Player, Nozzle}, events::*, common::{Random, point_in_circle}, GameState};
+use crate::{resource::{InputValues, Stats, Headless}, component::{Dashing, Invulnerable, Player, Nozzle, Recoil}, events::*, common::{Random, point_in_circle}, vacuum::VacuumRange, GameSet, GameState, playing};
 
 pub struct PlayerPlugin;
 
@@ -10,15 +11,20 @@ impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<PlayerDied>()
             .add_systems(OnEnter(GameState::Game), (spawn_player, reset_stats))
+            // a game that is restarted or left from the pause menu still has its player
+            .add_systems(OnExit(GameState::Game), despawn_player)
             .add_systems(Update, (
                 move_player,
                 regenerate_health,
                 handle_vacuum,
                 read_damage,
+                handle_between_waves,
+            ).chain().in_set(GameSet::Player).run_if(playing))
+            .add_systems(Update, (
                 spawn_vacuum_effect,
                 move_vacuum_effect,
-                handle_between_waves,
-            ).run_if(in_state(GameState::Game)));
+                flash_invulnerable,
+            ).run_if(playing.and_then(not(resource_exists::<Headless>()))));
     }
 }
 
@@ -36,15 +42,8 @@ fn spawn_player(
                 match entity.get::<Name>().map(|t| t.as_str()) {
                     Some("Nozzle") => {
                         cmds.with_children( |parent| {
-                            // let (vertices, indices) = create_vacuum_range();
-                            // let col = Collider::trimesh(vertices, indices);
-                            parent.spawn(Collider::from(ColliderShape::cone(1.0, 1.0)))
-                            //parent.spawn(col)
-                            //.insert(RigidBody::KinematicPositionBased)
-                            .insert(Nozzle)
-                            .insert(ColliderDisabled)
-                            .insert(TransformBundle::from(Transform::from_xyz(-0.25, -0.25, 0.0)))
-                            .insert(CollisionGroups::new(Group::GROUP_3, Group::GROUP_2 | Group::GROUP_5));
+                            // the range reaches forward from here along -Y, longer ranges only reach further out
+                            spawn_nozzle(parent, Transform::from_xyz(-0.25, 0.75, 0.0));
                         });
 
                         cmds
@@ -74,38 +73,77 @@ fn spawn_player(
     });
 }
 
+fn despawn_player(
+    query: Query<Entity, With<Player>>,
+    mut commands: Commands,
+) {
+    for player in &query {
+        commands.entity(player).despawn_recursive();
+    }
+}
+
+/// Spawns the vacuum range collider as a child of the given parent, upgrades resize it later on
+pub fn spawn_nozzle(
+    parent: &mut ChildBuilder,
+    transform: Transform,
+) {
+    let range = VacuumRange::default();
+    parent.spawn(range.collider())
+    //.insert(RigidBody::KinematicPositionBased)
+    .insert(range)
+    .insert(Nozzle)
+    .insert(ColliderDisabled)
+    .insert(TransformBundle::from(transform))
+    .insert(CollisionGroups::new(Group::GROUP_3, Group::GROUP_2 | Group::GROUP_5));
+}
+
 fn reset_stats(
     mut commands: Commands,
 ) {
     commands.insert_resource(Stats::new());
 }
 
+/// Speed the player is pushed away from a hit with
+const KNOCKBACK_IMPULSE: f32 = 8.0;
+/// How quickly the recoil of a hit fades out
+const RECOIL_DAMPING: f32 = 8.0;
+const FLASH_FREQUENCY: f32 = 10.0;
+
+#[allow(clippy::type_complexity)]
 fn move_player(
+    time: Res<Time>,
     stats: Res<Stats>,
     input_values: Res<InputValues>,
-    mut query: Query<(&mut Velocity, &mut Transform), With<Player>>,
+    mut query: Query<(&mut Velocity, &mut Transform, Option<&mut Recoil>, Entity), (With<Player>, Without<Dashing>)>,
+    mut commands: Commands,
 ) {
-    for (mut velocity, mut transform) in &mut query {
-        velocity.linvel = Vec3::new(input_values.movement.x, 0.0, input_values.movement.y) * stats.movement_speed * stats.normalized_health();
+    for (mut velocity, mut transform, recoil, entity) in &mut query {
+        velocity.linvel = Vec3::new(input_values.movement.x, 0.0, input_values.movement.y) * stats.movement_speed * (1.0 - stats.slow());
+        if let Some(mut recoil) = recoil {
+            velocity.linvel += recoil.0;
+            recoil.0 *= (-RECOIL_DAMPING * time.delta_seconds()).exp();
+            if recoil.0.length_squared() < 0.01 {
+                commands.entity(entity).remove::<Recoil>();
+            }
+        }
         transform.look_at(input_values.mouse_position, Vec3::Y);
     }
 }
 
+/// The nozzle collider is active while the button is held, unless the vacuum is overheated or the tank is full
 fn handle_vacuum(
-    mut mouse_events: EventReader<VacuumEvent>,
-    query: Query<Entity, With<Nozzle>>,
+    stats: Res<Stats>,
+    input_values: Res<InputValues>,
+    query: Query<(Entity, Has<ColliderDisabled>), With<Nozzle>>,
     mut commands: Commands,
 ) {
-    for e in &query {
-        for events in mouse_events.read() {
-            match events {
-                VacuumEvent::Start => {
-                    commands.entity(e).remove::<ColliderDisabled>();
-                }
-                VacuumEvent::Stop => {
-                    commands.entity(e).insert(ColliderDisabled);
-                }
-            }
+    let active = input_values.mouse_pressed && stats.can_vacuum();
+    for (e, disabled) in &query {
+        if active && disabled {
+            commands.entity(e).remove::<ColliderDisabled>();
+        }
+        else if !active && !disabled {
+            commands.entity(e).insert(ColliderDisabled);
         }
     }
 }
@@ -114,24 +152,23 @@ fn handle_vacuum(
 struct VacuumParticle;
 
 fn spawn_vacuum_effect(
+    stats: Res<Stats>,
     input_values: Res<InputValues>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    query: Query<&GlobalTransform, With<Nozzle>>,
+    query: Query<(&GlobalTransform, &VacuumRange), With<Nozzle>>,
     mut commands: Commands,
 ) {
-    let Ok(global) = query.get_single() else {
+    let Ok((global, range)) = query.get_single() else {
         return;
     };
 
-    let (_, _, translation) = global.to_scale_rotation_translation();
+    // particles appear on the far end of the range
+    let centerpoint = global.transform_point(range.front());
+    let (sin, cos) = point_in_circle(&mut thread_rng(), 1.0);
+    let point_in_circle = centerpoint + global.forward() * sin * range.depth / 2.0 + global.left() * cos * range.width / 2.0;
 
-    // we have to use down here as forward because the nozzle is rotated by 90°
-    let centerpoint = translation + global.down();
-    let (sin, cos) = point_in_circle(1.0);
-    let point_in_circle = centerpoint + global.forward() * sin + global.left() * cos;
-
-    if input_values.mouse_pressed {
+    if input_values.mouse_pressed && stats.can_vacuum() {
         commands.spawn(PbrBundle {
             mesh: meshes.add(Mesh::from(shape::Cube::new(0.1))),
             material: materials.add(StandardMaterial {
@@ -140,7 +177,7 @@ fn spawn_vacuum_effect(
                 ..default()
             }),
             // we have to use down here as forward because the nozzle is rotated by 90°
-            transform: Transform::from_translation(point_in_circle).with_rotation(Quat::random()),
+            transform: Transform::from_translation(point_in_circle).with_rotation(Quat::random(&mut thread_rng())),
             ..default()
         })
         .insert(Name::from("VacuumParticle"))
@@ -162,7 +199,7 @@ fn move_vacuum_effect(
         let distance = global.translation() - particle.translation + global.up() * 0.25;
 
         particle.translation += distance.normalize_or_zero() * time.delta_seconds() * distance.length_squared().max(1.0) * 2.5;
-        particle.rotation = Quat::random();
+        particle.rotation = Quat::random(&mut thread_rng());
         if distance.length_squared() < 0.01 {
             commands.entity(entity).despawn();
         }
@@ -178,22 +215,51 @@ fn regenerate_health(
     }
 }
 
+/// Only the first hit counts, the player is invulnerable and pushed away from it afterwards.
+/// Hits that arrive while the player is invulnerable are dropped, ghosts that are still touching it hit it again once it isn't.
 fn read_damage(
     mut stats: ResMut<Stats>,
     mut damage_events: EventReader<DamageEvent>,
     mut game_state: ResMut<NextState<GameState>>,
-    query: Query<Entity, With<Player>>,
+    query: Query<(&Transform, Has<Invulnerable>, Entity), With<Player>>,
     mut commands: Commands,
 ) {
-    for damage_event in damage_events.read() {
-        stats.health -= damage_event.0;
-        if stats.health <= 0.0 {
-            stats.reg_paused = true;
-            for player in &query {
-                commands.entity(player).despawn_recursive();
-            }
-            stats.health = 0.0;
-            game_state.set(GameState::GameOver);
+    // all hits of this frame are read, so none of them are left over for the next one
+    let Some((damage, source)) = damage_events.read().map(|e| (e.damage, e.source)).reduce(|first, _| first) else {
+        return;
+    };
+    let Ok((transform, invulnerable, player)) = query.get_single() else {
+        return;
+    };
+    if invulnerable {
+        return;
+    }
+
+    stats.health -= damage;
+    if stats.health <= 0.0 {
+        stats.reg_paused = true;
+        commands.entity(player).despawn_recursive();
+        stats.health = 0.0;
+        game_state.set(GameState::GameOver);
+        return;
+    }
+
+    let mut direction = transform.translation - source;
+    direction.y = 0.0;
+    commands.entity(player)
+        .insert(Invulnerable(Timer::from_seconds(stats.invulnerability_time, TimerMode::Once)))
+        .insert(Recoil(direction.normalize_or_zero() * KNOCKBACK_IMPULSE));
+}
+
+/// Lets the player model blink while it can't be hurt
+fn flash_invulnerable(
+    mut query: Query<(&mut Visibility, Option<&Invulnerable>), With<Player>>,
+) {
+    for (mut visibility, invulnerable) in &mut query {
+        let hidden = invulnerable.is_some_and(|i| !((i.0.elapsed_secs() * FLASH_FREQUENCY) as u32).is_multiple_of(2));
+        let target = if hidden { Visibility::Hidden } else { Visibility::Inherited };
+        if *visibility != target {
+            *visibility = target;
         }
     }
 }
@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::*;
+
+use crate::GameState;
+
+const SEED_ENV: &str = "GHOST_SUCKERS_SEED";
+
+/// Provides the single entropy source every gameplay system draws from.
+/// The seed is taken from `--seed <number>` or the `GHOST_SUCKERS_SEED` environment variable
+/// and falls back to a random one, which is logged so the run can be reproduced.
+pub struct RandomPlugin;
+
+impl Plugin for RandomPlugin {
+    fn build(&self, app: &mut App) {
+        // a seed inserted beforehand, e.g. by a replay, takes precedence
+        let seed = app.world.get_resource::<RngSeed>()
+            .copied()
+            .or_else(RngSeed::from_args)
+            .unwrap_or_else(|| RngSeed(rand::random()));
+        info!("using rng seed {}", seed.0);
+
+        app.add_plugins(EntropyPlugin::<ChaCha8Rng>::with_seed(seed.to_bytes()))
+            .insert_resource(seed)
+            .add_systems(OnEnter(GameState::Game), reseed);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Resource)]
+pub struct RngSeed(pub u64);
+
+impl RngSeed {
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        args.iter()
+            .position(|arg| arg == "--seed")
+            .and_then(|index| args.get(index + 1))
+            .cloned()
+            .or_else(|| std::env::var(SEED_ENV).ok())
+            .and_then(|value| value.parse().ok())
+            .map(Self)
+    }
+
+    pub fn to_bytes(self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        bytes[..8].copy_from_slice(&self.0.to_le_bytes());
+        bytes
+    }
+}
+
+/// Every game starts from the same seed, so a restart replays the same ghosts and upgrades
+fn reseed(
+    seed: Res<RngSeed>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+) {
+    rng.reseed(seed.to_bytes());
+}
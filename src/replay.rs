@@ -0,0 +1,254 @@
+use std::{fs, time::Duration};
+
+use bevy::{prelude::*, app::AppExit, asset::LoadState, ecs::schedule::ExecutorKind, time::TimeUpdateStrategy};
+use serde::{Deserialize, Serialize};
+
+use crate::{events::VacuumEvent, input::InputSet, random::RngSeed, resource::InputValues, waves::Waves, GameState, playing};
+
+/// Records the input of a game into a replay file with `--record <path>`
+/// or plays a replay file back instead of reading keyboard and mouse with `--replay <path>`
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let args: Vec<String> = std::env::args().collect();
+        let argument = |name: &str| {
+            args.iter()
+                .position(|arg| arg == name)
+                .and_then(|index| args.get(index + 1))
+                .cloned()
+        };
+
+        if let Some(path) = argument("--replay") {
+            let replay = Replay::load(&path).unwrap_or_else(|e| panic!("could not load replay {}: {}", path, e));
+            info!("playing back {} frames from {}", replay.frames.len(), path);
+            play_back(app, replay);
+        }
+        else if let Some(path) = argument("--record") {
+            info!("recording replay to {}", path);
+            record(app, path);
+        }
+    }
+}
+
+fn play_back(app: &mut App, replay: Replay) {
+    app.insert_resource(RngSeed(replay.seed))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(replay.start_delta)))
+        .insert_resource(ReplayPlayback::new(replay))
+        .add_systems(Update, start_game.run_if(in_state(GameState::Menu)))
+        .add_systems(Last, advance_time.run_if(in_state(GameState::Game)));
+    run_single_threaded(app);
+}
+
+fn record(app: &mut App, path: String) {
+    app.insert_resource(ReplayRecorder::new(path))
+        .add_systems(OnEnter(GameState::Game), start_recording)
+        .add_systems(PreUpdate, record_frame.after(InputSet).run_if(playing))
+        .add_systems(OnEnter(GameState::GameOver), save_recording)
+        .add_systems(Last, save_on_exit);
+    run_single_threaded(app);
+}
+
+/// The multithreaded executor may reorder systems between runs, which would break determinism
+fn run_single_threaded(app: &mut App) {
+    app.edit_schedule(Update, |schedule| {
+        schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+    });
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ReplayFrame {
+    pub delta: f32,
+    pub movement: [f32; 2],
+    pub mouse_pressed: bool,
+    pub mouse_position: [f32; 3],
+    pub vacuum_events: Vec<VacuumEvent>,
+    #[serde(default)]
+    pub dumping: bool,
+    #[serde(default)]
+    pub dash: bool,
+}
+
+impl ReplayFrame {
+    pub fn apply(&self, input_values: &mut InputValues) {
+        input_values.movement = Vec2::from_array(self.movement);
+        input_values.mouse_pressed = self.mouse_pressed;
+        input_values.mouse_position = Vec3::from_array(self.mouse_position);
+        input_values.dumping = self.dumping;
+        input_values.dash = self.dash;
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Replay {
+    pub seed: u64,
+    /// The delta of the frame the game was started in, which runs before the first recorded input
+    pub start_delta: f32,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        ron::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())?;
+        fs::write(path, content).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    current_frame: usize,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            current_frame: 0,
+        }
+    }
+
+    /// Returns the input of the current frame and advances to the next one
+    pub fn next_frame(&mut self) -> Option<&ReplayFrame> {
+        let frame = self.replay.frames.get(self.current_frame);
+        if frame.is_some() {
+            self.current_frame += 1;
+        }
+        frame
+    }
+
+    pub fn finished(&self) -> bool {
+        self.current_frame >= self.replay.frames.len()
+    }
+}
+
+#[derive(Resource)]
+struct ReplayRecorder {
+    path: String,
+    replay: Replay,
+}
+
+impl ReplayRecorder {
+    fn new(path: String) -> Self {
+        Self {
+            path,
+            replay: Replay::default(),
+        }
+    }
+
+    fn save(&self) {
+        match self.replay.save(&self.path) {
+            Ok(()) => info!("saved {} frames to {}", self.replay.frames.len(), self.path),
+            Err(e) => error!("could not save replay to {}: {}", self.path, e),
+        }
+    }
+}
+
+/// Starts the game once the wave definitions are loaded, otherwise the first wave would depend on how fast they load
+pub fn start_game(
+    asset_server: Res<AssetServer>,
+    waves: Res<Waves>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if matches!(asset_server.get_load_state(&waves.0), Some(LoadState::Loaded) | Some(LoadState::Failed)) {
+        game_state.set(GameState::Game);
+    }
+}
+
+/// Feeds the recorded frame time of the upcoming frame into the clock
+fn advance_time(
+    playback: Res<ReplayPlayback>,
+    mut time_update_strategy: ResMut<TimeUpdateStrategy>,
+) {
+    if let Some(frame) = playback.replay.frames.get(playback.current_frame) {
+        *time_update_strategy = TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(frame.delta));
+    }
+}
+
+fn start_recording(
+    time: Res<Time>,
+    seed: Res<RngSeed>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    recorder.replay = Replay {
+        seed: seed.0,
+        start_delta: time.delta_seconds(),
+        frames: Vec::new(),
+    };
+}
+
+fn record_frame(
+    time: Res<Time>,
+    input_values: Res<InputValues>,
+    mut vacuum_events: EventReader<VacuumEvent>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    recorder.replay.frames.push(ReplayFrame {
+        delta: time.delta_seconds(),
+        movement: input_values.movement.to_array(),
+        mouse_pressed: input_values.mouse_pressed,
+        mouse_position: input_values.mouse_position.to_array(),
+        vacuum_events: vacuum_events.read().copied().collect(),
+        dumping: input_values.dumping,
+        dash: input_values.dash,
+    });
+}
+
+fn save_recording(
+    recorder: Res<ReplayRecorder>,
+) {
+    recorder.save();
+}
+
+fn save_on_exit(
+    recorder: Res<ReplayRecorder>,
+    game_state: Res<State<GameState>>,
+    mut exit_events: EventReader<AppExit>,
+) {
+    // a game that ended has already been saved when entering the game over screen
+    if exit_events.read().next().is_some() && *game_state.get() == GameState::Game {
+        recorder.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{enemy_spawner::GhostSpawnConfig, headless::{tests::play, HeadlessConfig, HeadlessPlugin}, resource::Stats};
+
+    /// The stats and wave progress a game ended with
+    fn outcome(app: &App) -> String {
+        format!("{:?} {:?}", app.world.resource::<Stats>(), app.world.resource::<GhostSpawnConfig>())
+    }
+
+    #[test]
+    fn replay_ends_like_the_recording() {
+        let path = std::env::temp_dir().join(format!("ghost_suckers_replay_{}.ron", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let config = HeadlessConfig { max_waves: 1, ..default() };
+
+        let mut recording = App::new();
+        recording.insert_resource(RngSeed(7));
+        record(&mut recording, path.clone());
+        recording.add_plugins(HeadlessPlugin(config.clone()));
+        play(&mut recording);
+
+        let replay = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(replay.seed, 7);
+        assert!(!replay.frames.is_empty());
+
+        let mut playback = App::new();
+        play_back(&mut playback, replay);
+        playback.add_plugins(HeadlessPlugin(config));
+        play(&mut playback);
+
+        assert!(playback.world.resource::<ReplayPlayback>().finished());
+        assert_eq!(outcome(&playback), outcome(&recording));
+    }
+}
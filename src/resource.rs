@@ -2,11 +2,16 @@ use std::time::Duration;
 
 use bevy::prelude::*;
 
+use crate::vacuum::VacuumRange;
+
 #[derive(Resource)]
 pub struct InputValues {
     pub movement: Vec2,
     pub mouse_pressed: bool,
     pub mouse_position: Vec3,
+    pub dumping: bool,
+    /// Whether the dash button has been pressed this frame
+    pub dash: bool,
 }
 
 impl InputValues {
@@ -14,11 +19,17 @@ impl InputValues {
         Self {
             movement: Vec2::default(),
             mouse_pressed: false,
-            mouse_position: Vec3::default()
+            mouse_position: Vec3::default(),
+            dumping: false,
+            dash: false,
         }
     }
 }
 
+/// Marks an app that runs without a window, renderer, audio or ui
+#[derive(Resource)]
+pub struct Headless;
+
 #[derive(Default, Resource)]
 pub struct CameraSettings {
     pub translational_shake: f32,
@@ -56,9 +67,29 @@ pub struct Stats {
     pub max_health: f32,
     pub health: f32,
     pub regeneration: f32,
+    /// Seconds the player can't be hurt after a hit
+    pub invulnerability_time: f32,
     pub sucked_ghosts: u32,
     pub suck_time: f32,
     pub movement_speed: f32,
+    /// Fraction of the movement speed that is left at no health
+    pub min_speed: f32,
+    /// Shape of the slow from missing health, above 1 the slow sets in late, below 1 it sets in early
+    pub slow_curve: f32,
+    pub dash_distance: f32,
+    /// Seconds until the player can dash again
+    pub dash_cooldown: f32,
+    pub range: VacuumRange,
+    /// Seconds the vacuum has been running, it overheats once this reaches the capacity
+    pub heat: f32,
+    pub heat_capacity: f32,
+    /// Heat lost per second while the vacuum is off
+    pub cooldown_speed: f32,
+    pub overheated: bool,
+    /// Ghosts captured since the tank was last emptied
+    pub tank: u32,
+    pub tank_capacity: u32,
+    pub score: u32,
     pub reg_paused: bool,
 }
 
@@ -68,9 +99,22 @@ impl Stats {
             max_health: 100.0,
             health: 100.0,
             regeneration: 1.0,
+            invulnerability_time: 1.0,
             sucked_ghosts: 0,
             suck_time: 0.5,
             movement_speed: 5.0,
+            min_speed: 0.5,
+            slow_curve: 2.0,
+            dash_distance: 3.0,
+            dash_cooldown: 1.5,
+            range: VacuumRange::default(),
+            heat: 0.0,
+            heat_capacity: 4.0,
+            cooldown_speed: 2.0,
+            overheated: false,
+            tank: 0,
+            tank_capacity: 10,
+            score: 0,
             reg_paused: false,
         }
     }
@@ -79,6 +123,30 @@ impl Stats {
         self.health / self.max_health
     }
 
+    /// Fraction of the movement speed lost to missing health
+    pub fn slow(&self) -> f32 {
+        let missing_health = 1.0 - self.normalized_health();
+        (1.0 - self.min_speed) * missing_health.clamp(0.0, 1.0).powf(self.slow_curve)
+    }
+
+    /// The vacuum runs unless it is overheated, a full tank only keeps ghosts from being vacuumed up
+    pub fn can_vacuum(&self) -> bool {
+        !self.overheated
+    }
+
+    pub fn tank_full(&self) -> bool {
+        self.tank >= self.tank_capacity
+    }
+
+    pub fn capture_ghost(&mut self) {
+        self.sucked_ghosts += 1;
+        self.tank += 1;
+    }
+
+    pub fn normalized_heat(&self) -> f32 {
+        (self.heat / self.heat_capacity).clamp(0.0, 1.0)
+    }
+
     pub fn regenerate(&mut self, value: f32) {
         self.health = (self.health + value * self.regeneration).clamp(0.0, self.max_health);
     }
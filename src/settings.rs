@@ -0,0 +1,459 @@
+use std::{fs, path::PathBuf};
+
+use bevy::{prelude::*, window::{PresentMode, PrimaryWindow, WindowMode}};
+use serde::{Deserialize, Serialize};
+
+use crate::{ui::{NORMAL_BUTTON, HOVERED_BUTTON, PRESSED_BUTTON}, GameState, PauseState};
+
+/// Loads the settings from the config dir, applies them and shows the settings screen
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<OpenSettings>()
+            .add_systems(Update, (
+                apply_window_settings.run_if(resource_changed::<Settings>()),
+                open_settings_menu,
+                handle_settings_menu,
+                update_settings_labels,
+            ))
+            // the settings screen belongs to the menu it was opened from
+            .add_systems(OnExit(GameState::Menu), close_settings_menu)
+            .add_systems(OnExit(PauseState::Paused), close_settings_menu);
+    }
+}
+
+/// Opens the settings screen on top of the current menu
+#[derive(Event)]
+pub struct OpenSettings;
+
+const RESOLUTIONS: [(f32, f32); 4] = [(1024.0, 576.0), (1280.0, 720.0), (1600.0, 900.0), (1920.0, 1080.0)];
+const VOLUME_STEP: f32 = 0.1;
+const SHAKE_STEP: f32 = 0.25;
+const MAX_SHAKE: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum DisplayMode {
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+#[derive(Clone, Debug, Deserialize, Resource, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    /// Volume of menu sounds
+    pub ui_volume: f32,
+    pub display_mode: DisplayMode,
+    /// Index into the supported resolutions
+    pub resolution: usize,
+    pub vsync: bool,
+    /// Multiplies the strength of the camera shake, 0 turns it off
+    pub camera_shake: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            ui_volume: 1.0,
+            display_mode: DisplayMode::Windowed,
+            resolution: 0,
+            vsync: true,
+            camera_shake: 1.0,
+        }
+    }
+}
+
+impl Settings {
+    /// `settings.ron` in the `ghost_suckers` folder of the config dir of the user
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("ghost_suckers").join("settings.ron"))
+    }
+
+    /// Falls back to the default settings if there is no settings file or it can't be read
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => ron::from_str(&content).map(Self::clamped).unwrap_or_else(|e| {
+                warn!("could not parse settings {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => {
+                info!("no settings at {}, using the defaults", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    /// A hand edited file may hold values the settings screen would never set
+    fn clamped(mut self) -> Self {
+        self.master_volume = self.master_volume.clamp(0.0, 1.0);
+        self.music_volume = self.music_volume.clamp(0.0, 1.0);
+        self.sfx_volume = self.sfx_volume.clamp(0.0, 1.0);
+        self.ui_volume = self.ui_volume.clamp(0.0, 1.0);
+        self.camera_shake = self.camera_shake.clamp(0.0, MAX_SHAKE);
+        self
+    }
+
+    pub fn save(&self) {
+        let Some(path) = Self::path() else {
+            warn!("there is no config dir to save the settings to");
+            return;
+        };
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                fs::write(&path, content).map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(()) => info!("saved settings to {}", path.display()),
+            Err(e) => error!("could not save settings to {}: {}", path.display(), e),
+        }
+    }
+
+    pub fn resolution(&self) -> (f32, f32) {
+        RESOLUTIONS[self.resolution.min(RESOLUTIONS.len() - 1)]
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        match self.display_mode {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::Borderless => WindowMode::BorderlessFullscreen,
+            DisplayMode::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync { PresentMode::AutoVsync } else { PresentMode::AutoNoVsync }
+    }
+
+    /// Steps the setting one notch up or down
+    fn change(&mut self, setting: Setting, step: i32) {
+        let volume = |value: f32| (value + VOLUME_STEP * step as f32).clamp(0.0, 1.0);
+        match setting {
+            Setting::MasterVolume => self.master_volume = volume(self.master_volume),
+            Setting::MusicVolume => self.music_volume = volume(self.music_volume),
+            Setting::SfxVolume => self.sfx_volume = volume(self.sfx_volume),
+            Setting::UiVolume => self.ui_volume = volume(self.ui_volume),
+            Setting::DisplayMode => {
+                let modes = [DisplayMode::Windowed, DisplayMode::Borderless, DisplayMode::Fullscreen];
+                let index = modes.iter().position(|mode| *mode == self.display_mode).unwrap_or(0);
+                self.display_mode = modes[(index as i32 + step).rem_euclid(modes.len() as i32) as usize];
+            }
+            Setting::Resolution => {
+                self.resolution = (self.resolution as i32 + step).rem_euclid(RESOLUTIONS.len() as i32) as usize;
+            }
+            Setting::Vsync => self.vsync = !self.vsync,
+            Setting::CameraShake => {
+                self.camera_shake = (self.camera_shake + SHAKE_STEP * step as f32).clamp(0.0, MAX_SHAKE);
+            }
+        }
+    }
+
+    fn label(&self, setting: Setting) -> String {
+        match setting {
+            Setting::MasterVolume => format!("Master volume: {:.0}%", self.master_volume * 100.0),
+            Setting::MusicVolume => format!("Music volume: {:.0}%", self.music_volume * 100.0),
+            Setting::SfxVolume => format!("Sound volume: {:.0}%", self.sfx_volume * 100.0),
+            Setting::UiVolume => format!("UI volume: {:.0}%", self.ui_volume * 100.0),
+            Setting::DisplayMode => format!("Window: {:?}", self.display_mode),
+            Setting::Resolution => {
+                let (width, height) = self.resolution();
+                format!("Resolution: {}x{}", width, height)
+            }
+            Setting::Vsync => format!("VSync: {}", if self.vsync { "on" } else { "off" }),
+            Setting::CameraShake => format!("Camera shake: {:.0}%", self.camera_shake * 100.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Setting {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    UiVolume,
+    DisplayMode,
+    Resolution,
+    Vsync,
+    CameraShake,
+}
+
+const ALL_SETTINGS: [Setting; 8] = [
+    Setting::MasterVolume,
+    Setting::MusicVolume,
+    Setting::SfxVolume,
+    Setting::UiVolume,
+    Setting::DisplayMode,
+    Setting::Resolution,
+    Setting::Vsync,
+    Setting::CameraShake,
+];
+
+fn apply_window_settings(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+    let (width, height) = settings.resolution();
+    if window.resolution.width() != width || window.resolution.height() != height {
+        window.resolution.set(width, height);
+    }
+    if window.mode != settings.window_mode() {
+        window.mode = settings.window_mode();
+    }
+    if window.present_mode != settings.present_mode() {
+        window.present_mode = settings.present_mode();
+    }
+}
+
+#[derive(Component)]
+struct SettingsMenu;
+
+#[derive(Clone, Copy, Component)]
+enum SettingsButton {
+    Change(Setting, i32),
+    Back,
+}
+
+#[derive(Component)]
+struct SettingLabel(Setting);
+
+fn spawn_button(parent: &mut ChildBuilder, asset_server: &AssetServer, button: SettingsButton, text: &str, width: f32) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                width: Val::Px(width),
+                height: Val::Px(50.0),
+                border: UiRect::all(Val::Px(5.0)),
+                // horizontally center child text
+                justify_content: JustifyContent::Center,
+                // vertically center child text
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            border_color: BorderColor(Color::BLACK),
+            background_color: NORMAL_BUTTON.into(),
+            ..default()
+        })
+        .insert(button)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font: asset_server.load("graveyrd.ttf"),
+                    font_size: 35.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ));
+        });
+}
+
+fn open_settings_menu(
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    mut open_events: EventReader<OpenSettings>,
+    menus: Query<(), With<SettingsMenu>>,
+    mut commands: Commands,
+) {
+    if open_events.read().count() == 0 || !menus.is_empty() {
+        return;
+    }
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            // covers the menu it was opened from and keeps its buttons from being clicked
+            focus_policy: bevy::ui::FocusPolicy::Block,
+            z_index: ZIndex::Global(10),
+            ..default()
+        })
+        .insert(SettingsMenu)
+        .with_children(|parent| {
+            for setting in ALL_SETTINGS {
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(10.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_button(parent, &asset_server, SettingsButton::Change(setting, -1), "<", 50.0);
+                    parent.spawn(TextBundle::from_section(
+                        settings.label(setting),
+                        TextStyle {
+                            font: asset_server.load("graveyrd.ttf"),
+                            font_size: 35.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    ).with_style(Style {
+                        width: Val::Px(400.0),
+                        ..default()
+                    }))
+                    .insert(SettingLabel(setting));
+                    spawn_button(parent, &asset_server, SettingsButton::Change(setting, 1), ">", 50.0);
+                });
+            }
+            spawn_button(parent, &asset_server, SettingsButton::Back, "Back", 150.0);
+        });
+}
+
+fn handle_settings_menu(
+    mut settings: ResMut<Settings>,
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &SettingsButton,
+            &mut BackgroundColor,
+            &mut BorderColor,
+        ),
+        Changed<Interaction>,
+    >,
+    menus: Query<Entity, With<SettingsMenu>>,
+    mut commands: Commands,
+) {
+    for (interaction, button, mut color, mut border_color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PRESSED_BUTTON.into();
+                border_color.0 = Color::RED;
+                match *button {
+                    SettingsButton::Change(setting, step) => settings.change(setting, step),
+                    SettingsButton::Back => {
+                        settings.save();
+                        for menu in &menus {
+                            commands.entity(menu).despawn_recursive();
+                        }
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+                border_color.0 = Color::WHITE;
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+                border_color.0 = Color::BLACK;
+            }
+        }
+    }
+}
+
+fn update_settings_labels(
+    settings: Res<Settings>,
+    mut labels: Query<(&mut Text, &SettingLabel)>,
+) {
+    for (mut text, label) in &mut labels {
+        let value = settings.label(label.0);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+/// Leaving the menu the settings were opened from closes them as if Back had been pressed
+fn close_settings_menu(
+    settings: Res<Settings>,
+    menus: Query<Entity, With<SettingsMenu>>,
+    mut commands: Commands,
+) {
+    for menu in &menus {
+        settings.save();
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volumes_step_and_stay_in_range() {
+        let mut settings = Settings::default();
+        settings.change(Setting::MusicVolume, -1);
+        assert!((settings.music_volume - 0.9).abs() < 1e-6);
+        settings.change(Setting::MusicVolume, 1);
+        settings.change(Setting::MusicVolume, 1);
+        assert_eq!(settings.music_volume, 1.0);
+
+        for _ in 0..20 {
+            settings.change(Setting::SfxVolume, -1);
+        }
+        assert_eq!(settings.sfx_volume, 0.0);
+        // the other volumes are left alone
+        assert_eq!(settings.master_volume, 1.0);
+        assert_eq!(settings.ui_volume, 1.0);
+    }
+
+    #[test]
+    fn display_mode_and_resolution_wrap_around() {
+        let mut settings = Settings::default();
+        settings.change(Setting::DisplayMode, -1);
+        assert_eq!(settings.display_mode, DisplayMode::Fullscreen);
+        settings.change(Setting::DisplayMode, 1);
+        assert_eq!(settings.display_mode, DisplayMode::Windowed);
+
+        settings.change(Setting::Resolution, -1);
+        assert_eq!(settings.resolution, RESOLUTIONS.len() - 1);
+        settings.change(Setting::Resolution, 1);
+        assert_eq!(settings.resolution, 0);
+    }
+
+    #[test]
+    fn vsync_toggles_either_way() {
+        let mut settings = Settings::default();
+        settings.change(Setting::Vsync, 1);
+        assert!(!settings.vsync);
+        settings.change(Setting::Vsync, -1);
+        assert!(settings.vsync);
+    }
+
+    #[test]
+    fn camera_shake_stays_in_range() {
+        let mut settings = Settings::default();
+        for _ in 0..10 {
+            settings.change(Setting::CameraShake, 1);
+        }
+        assert_eq!(settings.camera_shake, MAX_SHAKE);
+        for _ in 0..10 {
+            settings.change(Setting::CameraShake, -1);
+        }
+        assert_eq!(settings.camera_shake, 0.0);
+    }
+
+    #[test]
+    fn loaded_values_are_clamped() {
+        let settings: Settings = ron::from_str("(master_volume: 3.0, sfx_volume: -1.0, ui_volume: 2.0, camera_shake: 10.0)").unwrap();
+        let settings = settings.clamped();
+        assert_eq!(settings.master_volume, 1.0);
+        assert_eq!(settings.music_volume, 1.0);
+        assert_eq!(settings.sfx_volume, 0.0);
+        assert_eq!(settings.ui_volume, 1.0);
+        assert_eq!(settings.camera_shake, MAX_SHAKE);
+    }
+}
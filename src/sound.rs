@@ -1,201 +1,143 @@
-use bevy::{prelude::*, audio::{VolumeLevel, PlaybackMode}};
+use bevy::{prelude::*, audio::SpatialAudioSink};
 
-use crate::{resource::Stats, component::Ghost, enemy_spawner::GhostSpawnConfig, events::{VacuumEvent, WaveEnd, PickedUpgrade, Sucked, DamageEvent}, GameState};
+use crate::{resource::{InputValues, Stats}, cue::{Cue, PlayCue}, mixer::{Bus, Channel, Mixer}, events::{VacuumEvent, PickedUpgrade, DamageEvent, HeatEvent, TankEmptied}, GameState, PauseState, playing};
 
 pub struct SoundPlugin;
 
 impl Plugin for SoundPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(DangerLevel::new())
-            .add_systems(OnEnter(GameState::Menu), play_base_track)
-            .add_systems(OnExit(GameState::Menu), stop_base_track)
-            .add_systems(OnEnter(GameState::Game), setup)
-            .add_systems(Update, (
-                update_danger_level,
+        app.add_systems(Update, (
                 start_stop_vacuum,
-                apply_danger_level,
+                play_heat_sounds,
+                play_tank_sounds,
+                sync_vacuum_sound,
                 check_vacuum_sound,
-                check_wave_end,
                 play_upgrade_sound,
-                suck_ghosts,
                 hurt,
-            ).run_if(in_state(GameState::Game)))
+            ).run_if(playing))
+            .add_systems(Update, play_button_sounds)
+            .add_systems(OnEnter(PauseState::Paused), duck_music)
+            .add_systems(OnExit(PauseState::Paused), unduck_music)
+            .add_systems(OnExit(GameState::Game), kill_all_sound)
             .add_systems(OnEnter(GameState::GameOver), kill_all_sound)
             .add_systems(OnExit(GameState::GameOver), kill_all_sound);
     }
 }
 
-fn play_base_track(
-    asset_server: Res<AssetServer>,
-    mut commands: Commands,
-) {
-    commands.spawn(AudioBundle {
-        source: asset_server.load("sounds/Basetrack.wav"),
-        settings: PlaybackSettings {
-            mode: PlaybackMode::Loop,
-            ..default()
-        },
-        ..default()
-    })
-    .insert(BaseTrack);
-}
+/// The sound effects are a lot louder than the music
+const SFX_GAIN: f32 = 0.5;
 
-fn stop_base_track(
-    query: Query<Entity, With<BaseTrack>>,
-    mut commands: Commands,
-) {
-    for entity in &query {
-        commands.entity(entity).despawn_recursive();
-    }
+fn sfx() -> Channel {
+    Channel::new(Bus::Sfx, SFX_GAIN)
 }
 
 #[derive(Component)]
-struct BaseTrack;
+struct VacuumSound;
+
 #[derive(Component)]
-struct MediumTrack;
+struct VacuumStart;
 
-fn setup(
+fn start_stop_vacuum(
+    mixer: Res<Mixer>,
     asset_server: Res<AssetServer>,
+    stats: Res<Stats>,
+    mut vacuum_events: EventReader<VacuumEvent>,
+    mut cues: EventWriter<PlayCue>,
+    vacuum_sounds: Query<Entity, With<VacuumSound>>,
     mut commands: Commands,
 ) {
-    commands.spawn(AudioBundle {
-        source: asset_server.load("sounds/Basetrack.wav"),
-        settings: PlaybackSettings {
-            mode: PlaybackMode::Loop,
-            ..default()
-        },
-        ..default()
-    })
-    .insert(BaseTrack);
-
-    commands.spawn(AudioBundle {
-        source: asset_server.load("sounds/Medium Track.wav"),
-        settings: PlaybackSettings {
-            volume: bevy::audio::Volume::Relative(VolumeLevel::new(0.0)),
-            mode: PlaybackMode::Loop,
-            ..default()
-        },
-        ..default()
-    })
-    .insert(MediumTrack);
-}
-
-#[derive(Resource)]
-struct DangerLevel {
-    remaining_health: f32,
-    ghosts_on_screen: f32,
-    health_weight: f32,
-    ghost_weight: f32,
-    base_to_medium_threshold: (f32, f32),
-}
-
-impl DangerLevel {
-    pub fn new() -> Self {
-        Self {
-            remaining_health: 1.0,
-            ghosts_on_screen: 0.0,
-            health_weight: 2.0,
-            ghost_weight: 1.0,
-            base_to_medium_threshold: (0.32, 0.35),
+    for vacuum_event in vacuum_events.read() {
+        if let VacuumEvent::Blow = vacuum_event {
+            if stats.tank > 0 {
+                cues.send(PlayCue::new(Cue::Blow));
+            }
+            continue;
+        }
+        // a vacuum that can't run stays silent until it can again
+        if !stats.can_vacuum() {
+            continue;
+        }
+        if let Ok(vacuum_sound) = vacuum_sounds.get_single() {
+            commands.entity(vacuum_sound).despawn_recursive();
+        }
+        match vacuum_event {
+            VacuumEvent::Start => {
+                spawn_vacuum_start(&asset_server, &mixer, &mut commands);
+            }
+            VacuumEvent::Stop => {
+                commands.spawn(mixer.audio(asset_server.load("sounds/vacuum_stop.wav"), sfx(), PlaybackSettings::DESPAWN))
+                    .insert(VacuumSound);
+            }
+            VacuumEvent::Blow => {}
         }
-    }
-
-    pub fn update(&mut self, remaining_health: f32, ghosts_on_screen: f32) {
-        self.remaining_health = remaining_health;
-        self.ghosts_on_screen = ghosts_on_screen;
-    }
-
-    /// Returns the normalized danger level with weights applied
-    pub fn danger_level(&self) -> f32 {
-        let missing_health = 1.0 - self.remaining_health;
-        (missing_health * self.health_weight + self.ghosts_on_screen * self.ghost_weight) / (self.health_weight + self.ghost_weight)
     }
 }
 
-fn update_danger_level(
-    stats: Res<Stats>,
-    wave_config: Res<GhostSpawnConfig>,
-    mut danger_level: ResMut<DangerLevel>,
-    ghosts: Query<&Ghost>,
+fn spawn_vacuum_start(
+    asset_server: &AssetServer,
+    mixer: &Mixer,
+    commands: &mut Commands,
 ) {
-    let ghosts_on_screen = ghosts.iter().collect::<Vec<&Ghost>>().len();
-    danger_level.update(stats.normalized_health(), ghosts_on_screen as f32 / wave_config.wave_size() as f32);
+    commands.spawn(mixer.audio(asset_server.load("sounds/vacuum_start.wav"), sfx(), PlaybackSettings::ONCE))
+        .insert(VacuumSound)
+        .insert(VacuumStart);
 }
 
-fn apply_danger_level(
-    time: Res<Time>,
-    danger_level: Res<DangerLevel>,
-    base_tracks: Query<&AudioSink, (With<BaseTrack>, Without<MediumTrack>)>,
-    medium_tracks: Query<&AudioSink, (With<MediumTrack>, Without<BaseTrack>)>,
+fn play_heat_sounds(
+    mut heat_events: EventReader<HeatEvent>,
+    mut cues: EventWriter<PlayCue>,
 ) {
-    let Ok(base_track) = base_tracks.get_single() else {
-        return;
-    };
-    let Ok(medium_track) = medium_tracks.get_single() else {
-        return;
-    };
-
-    if danger_level.danger_level() > danger_level.base_to_medium_threshold.0 {
-        medium_track.set_volume((medium_track.volume() + time.delta_seconds()).clamp(0.0, 1.0));
-        base_track.set_volume((base_track.volume() - time.delta_seconds()).clamp(0.0, 1.0));
-    }
-
-    if danger_level.danger_level() < danger_level.base_to_medium_threshold.1 {
-        medium_track.set_volume((medium_track.volume() - time.delta_seconds()).clamp(0.0, 1.0));
-        base_track.set_volume((base_track.volume() + time.delta_seconds()).clamp(0.0, 1.0));
+    for heat_event in heat_events.read() {
+        let cue = match heat_event {
+            HeatEvent::Overheated => Cue::Overheat,
+            HeatEvent::Cooled => Cue::Cooled,
+        };
+        cues.send(PlayCue::new(cue));
     }
 }
 
-#[derive(Component)]
-struct VacuumSound;
-
-#[derive(Component)]
-struct VacuumStart;
-
-fn vacuum_volume() -> bevy::audio::Volume {
-    bevy::audio::Volume::Relative(VolumeLevel::new(0.5))
+fn play_tank_sounds(
+    mut tank_emptied_events: EventReader<TankEmptied>,
+    mut cues: EventWriter<PlayCue>,
+) {
+    for _ in tank_emptied_events.read() {
+        cues.send(PlayCue::new(Cue::TankEmptied));
+    }
 }
 
-fn start_stop_vacuum(
+/// Stops the vacuum sound while the button is held but the vacuum can't run and starts it again once it can
+fn sync_vacuum_sound(
+    mixer: Res<Mixer>,
     asset_server: Res<AssetServer>,
-    mut vacuum_events: EventReader<VacuumEvent>,
+    stats: Res<Stats>,
+    input_values: Res<InputValues>,
+    mut could_vacuum: Local<bool>,
     vacuum_sounds: Query<Entity, With<VacuumSound>>,
     mut commands: Commands,
 ) {
-    for vacuum_event in vacuum_events.read() {
-        if let Ok(vacuum_sound) = vacuum_sounds.get_single() {
-            commands.entity(vacuum_sound).despawn_recursive();
-        }
-        match vacuum_event {
-            VacuumEvent::Start => {
-                commands.spawn(AudioBundle {
-                    source: asset_server.load("sounds/vacuum_start.wav"),
-                    settings: PlaybackSettings {
-                        volume: vacuum_volume(),
-                        ..default()
-                    },
-                    ..default()
-                })
-                .insert(VacuumSound)
-                .insert(VacuumStart);
-            }
-            VacuumEvent::Stop => {
-                commands.spawn(AudioBundle {
-                    source: asset_server.load("sounds/vacuum_stop.wav"),
-                    settings: PlaybackSettings {
-                        mode: bevy::audio::PlaybackMode::Despawn,
-                        volume: vacuum_volume(),
-                        ..default()
-                    },
-                    ..default()
-                })
-                .insert(VacuumSound);
-            }
-        }
+    let can_vacuum = stats.can_vacuum();
+    if can_vacuum == *could_vacuum {
+        return;
+    }
+    *could_vacuum = can_vacuum;
+    if !input_values.mouse_pressed {
+        return;
+    }
+
+    for vacuum_sound in &vacuum_sounds {
+        commands.entity(vacuum_sound).despawn_recursive();
+    }
+    if can_vacuum {
+        spawn_vacuum_start(&asset_server, &mixer, &mut commands);
+    }
+    else {
+        commands.spawn(mixer.audio(asset_server.load("sounds/vacuum_stop.wav"), sfx(), PlaybackSettings::DESPAWN))
+            .insert(VacuumSound);
     }
 }
 
 fn check_vacuum_sound(
+    mixer: Res<Mixer>,
     asset_server: Res<AssetServer>,
     vacuum_starts: Query<(&AudioSink, Entity), With<VacuumStart>>,
     mut commands: Commands,
@@ -206,97 +148,88 @@ fn check_vacuum_sound(
 
     if vacuum_start.empty() {
         commands.entity(entity).despawn_recursive();
-        commands.spawn(AudioBundle {
-            source: asset_server.load("sounds/vacuum_running.wav"),
-            settings: PlaybackSettings {
-                mode: bevy::audio::PlaybackMode::Loop,
-                volume: vacuum_volume(),
-                ..default()
-            },
-            ..default()
-        })
-        .insert(VacuumSound);
+        commands.spawn(mixer.audio(asset_server.load("sounds/vacuum_running.wav"), sfx(), PlaybackSettings::LOOP))
+            .insert(VacuumSound);
     }
 }
 
-fn check_wave_end(
-    asset_server: Res<AssetServer>,
-    mut wave_end_event: EventReader<WaveEnd>,
-    mut commands: Commands,
+fn play_upgrade_sound(
+    mut events: EventReader<PickedUpgrade>,
+    mut cues: EventWriter<PlayCue>,
 ) {
-    for _ in wave_end_event.read() {
-
-        commands.spawn(AudioBundle {
-            source: asset_server.load("sounds/upgrade_sound.wav"),
-            settings: PlaybackSettings {
-                mode: bevy::audio::PlaybackMode::Despawn,
-                volume: vacuum_volume(),
-                ..default()
-            },
-            ..default()
-        });
+    for _ in events.read() {
+        cues.send(PlayCue::new(Cue::PickedUpgrade));
     }
 }
 
-fn play_upgrade_sound(
-    asset_server: Res<AssetServer>,
-    mut events: EventReader<PickedUpgrade>,
-    mut commands: Commands,
+fn hurt(
+    mut events: EventReader<DamageEvent>,
+    mut cues: EventWriter<PlayCue>,
 ) {
     for _ in events.read() {
-        commands.spawn(AudioBundle {
-            source: asset_server.load("sounds/blop.wav"),
-            settings: PlaybackSettings {
-                mode: bevy::audio::PlaybackMode::Despawn,
-                volume: vacuum_volume(),
-                ..default()
-            },
-            ..default()
-        });
+        cues.send(PlayCue::new(Cue::Hurt));
     }
 }
 
-fn suck_ghosts(
-    asset_server: Res<AssetServer>,
-    mut events: EventReader<Sucked>,
-    mut commands: Commands,
+/// Clicks for the buttons of every menu
+fn play_button_sounds(
+    interactions: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    mut cues: EventWriter<PlayCue>,
 ) {
-    for _ in events.read() {
-        commands.spawn(AudioBundle {
-            source: asset_server.load("sounds/suck_pop.wav"),
-            settings: PlaybackSettings {
-                mode: bevy::audio::PlaybackMode::Despawn,
-                volume: vacuum_volume(),
-                ..default()
-            },
-            ..default()
-        });
+    for interaction in &interactions {
+        if *interaction == Interaction::Pressed {
+            cues.send(PlayCue::new(Cue::ButtonPressed));
+        }
     }
 }
 
-fn hurt(
-    asset_server: Res<AssetServer>,
-    mut events: EventReader<DamageEvent>,
-    mut commands: Commands,
+/// Turns the music down and holds the sound effects while the game is paused, the ui keeps playing for the pause menu
+fn duck_music(
+    mut mixer: ResMut<Mixer>,
+    sinks: Query<(&AudioSink, &Channel)>,
+    spatial_sinks: Query<(&SpatialAudioSink, &Channel)>,
 ) {
-    for _ in events.read() {
-        commands.spawn(AudioBundle {
-            source: asset_server.load("sounds/oof.wav"),
-            settings: PlaybackSettings {
-                mode: bevy::audio::PlaybackMode::Despawn,
-                volume: vacuum_volume(),
-                ..default()
-            },
-            ..default()
-        });
+    mixer.ducked = true;
+    for (sink, channel) in &sinks {
+        if channel.bus == Bus::Sfx {
+            sink.pause();
+        }
+    }
+    for (sink, channel) in &spatial_sinks {
+        if channel.bus == Bus::Sfx {
+            sink.pause();
+        }
     }
 }
 
+fn unduck_music(
+    mut mixer: ResMut<Mixer>,
+    sinks: Query<(&AudioSink, &Channel)>,
+    spatial_sinks: Query<(&SpatialAudioSink, &Channel)>,
+) {
+    mixer.ducked = false;
+    for (sink, channel) in &sinks {
+        if channel.bus == Bus::Sfx {
+            sink.play();
+        }
+    }
+    for (sink, channel) in &spatial_sinks {
+        if channel.bus == Bus::Sfx {
+            sink.play();
+        }
+    }
+}
+
+/// Stops every sound but the ui, whose click on the button that changed the state would be cut off otherwise
+#[allow(clippy::type_complexity)]
 fn kill_all_sound(
-    query: Query<Entity, With<AudioSink>>,
+    query: Query<(Entity, Option<&Channel>), Or<(With<AudioSink>, With<SpatialAudioSink>)>>,
     mut commands: Commands,
 ) {
-    for sink in &query {
+    for (sink, channel) in &query {
+        if channel.is_some_and(|channel| channel.bus == Bus::Ui) {
+            continue;
+        }
         commands.entity(sink).despawn_recursive();
     }
-}
\ No newline at end of file
+}
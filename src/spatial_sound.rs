@@ -0,0 +1,171 @@
+use bevy::{prelude::*, audio::SpatialListener};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+
+use crate::{component::{FollowCamera, Ghost, Player}, cue::{Cue, PlayCue}, events::Sucked, mixer::{Bus, Channel, Mixer}, upgrade::Upgrade, GameState, playing};
+
+/// Plays ghost ambience, suck pops and humming dirtbags where they happen, heard from a listener on the player
+pub struct SpatialSoundPlugin;
+
+impl Plugin for SpatialSoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Game), spawn_listener)
+            .add_systems(OnExit(GameState::Game), despawn_listener)
+            .add_systems(Update, (
+                play_ghost_ambience,
+                hum_dirtbags,
+                pop_sucked,
+            ).run_if(playing))
+            .add_systems(Update, (
+                move_listener,
+                follow_emitters,
+            ).run_if(in_state(GameState::Game)));
+    }
+}
+
+/// World units per unit of the spatial audio, sounds start to get quieter at 1 / `SPATIAL_SCALE` units
+pub const SPATIAL_SCALE: f32 = 0.2;
+const EAR_GAP: f32 = 4.0;
+
+/// Sounds further away from the listener than this aren't started
+const AMBIENCE_RANGE: f32 = 15.0;
+const AMBIENCE_INTERVAL: (f32, f32) = (0.3, 1.2);
+const HUM_GAIN: f32 = 0.2;
+const HUM_SPEED: f32 = 0.5;
+/// Only the closest dirtbags hum, the voices of the cues are capped by their definitions
+const MAX_HUMS: usize = 2;
+
+#[derive(Component)]
+struct Listener;
+
+/// Moves the sound with the entity it is coming from, it stays where it is once the entity is gone
+#[derive(Component)]
+pub struct Follow(pub Entity);
+
+#[derive(Component)]
+struct Hum;
+
+fn spawn_listener(
+    mut commands: Commands,
+) {
+    commands.spawn(TransformBundle::default())
+        .insert(SpatialListener::new(EAR_GAP))
+        .insert(Name::from("Listener"))
+        .insert(Listener);
+}
+
+#[allow(clippy::type_complexity)]
+fn despawn_listener(
+    query: Query<Entity, Or<(With<Listener>, With<Follow>)>>,
+    mut commands: Commands,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// The listener sits on the player but faces like the camera, so sounds pan with the screen
+#[allow(clippy::type_complexity)]
+fn move_listener(
+    players: Query<&Transform, (With<Player>, Without<Listener>)>,
+    cameras: Query<&Transform, (With<FollowCamera>, Without<Listener>)>,
+    mut listeners: Query<&mut Transform, (With<Listener>, Without<Player>, Without<FollowCamera>)>,
+) {
+    let (Ok(player), Ok(mut listener)) = (players.get_single(), listeners.get_single_mut()) else {
+        return;
+    };
+    listener.translation = player.translation;
+    if let Ok(camera) = cameras.get_single() {
+        listener.rotation = camera.rotation;
+    }
+}
+
+fn follow_emitters(
+    targets: Query<&GlobalTransform, Without<Follow>>,
+    mut emitters: Query<(&mut Transform, &Follow)>,
+) {
+    for (mut transform, follow) in &mut emitters {
+        if let Ok(target) = targets.get(follow.0) {
+            transform.translation = target.translation();
+        }
+    }
+}
+
+/// Every now and then a random ghost close to the listener moans or whooshes
+fn play_ghost_ambience(
+    time: Res<Time>,
+    mut cooldown: Local<f32>,
+    listeners: Query<&GlobalTransform, With<Listener>>,
+    ghosts: Query<(&GlobalTransform, Entity), With<Ghost>>,
+    mut cues: EventWriter<PlayCue>,
+) {
+    *cooldown -= time.delta_seconds();
+    if *cooldown > 0.0 {
+        return;
+    }
+    let mut rng = thread_rng();
+    *cooldown = rng.gen_range(AMBIENCE_INTERVAL.0..AMBIENCE_INTERVAL.1);
+
+    let Ok(listener) = listeners.get_single() else {
+        return;
+    };
+    let nearby: Vec<(Vec3, Entity)> = ghosts.iter()
+        .map(|(transform, entity)| (transform.translation(), entity))
+        .filter(|(position, _)| position.distance(listener.translation()) < AMBIENCE_RANGE)
+        .collect();
+    let Some((position, ghost)) = nearby.choose(&mut rng) else {
+        return;
+    };
+
+    let cue = if rng.gen_bool(0.5) { Cue::GhostMoan } else { Cue::GhostWhoosh };
+    cues.send(PlayCue::at(cue, *position).following(*ghost));
+}
+
+/// The dirtbags closest to the listener hum until they are vacuumed up
+fn hum_dirtbags(
+    asset_server: Res<AssetServer>,
+    mixer: Res<Mixer>,
+    listeners: Query<&GlobalTransform, With<Listener>>,
+    dirtbags: Query<(&GlobalTransform, Entity), With<Upgrade>>,
+    hums: Query<(&Follow, Entity), With<Hum>>,
+    mut commands: Commands,
+) {
+    let Ok(listener) = listeners.get_single() else {
+        return;
+    };
+    let mut nearest: Vec<(f32, Vec3, Entity)> = dirtbags.iter()
+        .map(|(transform, entity)| (transform.translation().distance(listener.translation()), transform.translation(), entity))
+        .filter(|(distance, _, _)| *distance < AMBIENCE_RANGE)
+        .collect();
+    nearest.sort_by(|a, b| a.0.total_cmp(&b.0));
+    nearest.truncate(MAX_HUMS);
+
+    for (follow, hum) in &hums {
+        if !nearest.iter().any(|(_, _, dirtbag)| *dirtbag == follow.0) {
+            commands.entity(hum).despawn_recursive();
+        }
+    }
+    for (_, position, dirtbag) in nearest {
+        if hums.iter().any(|(follow, _)| follow.0 == dirtbag) {
+            continue;
+        }
+        commands.spawn(mixer.audio(asset_server.load("sounds/vacuum_running.wav"), Channel::new(Bus::Sfx, HUM_GAIN), PlaybackSettings::LOOP.with_speed(HUM_SPEED).with_spatial(true)))
+            .insert(TransformBundle::from_transform(Transform::from_translation(position)))
+            .insert(Follow(dirtbag))
+            .insert(Hum);
+    }
+}
+
+/// Pops where the vacuumed entity was, or on the listener if it is already gone
+fn pop_sucked(
+    mut events: EventReader<Sucked>,
+    listeners: Query<&GlobalTransform, With<Listener>>,
+    transforms: Query<&GlobalTransform>,
+    mut cues: EventWriter<PlayCue>,
+) {
+    for event in events.read() {
+        let Some(position) = transforms.get(event.0).or_else(|_| listeners.get_single()).ok().map(|t| t.translation()) else {
+            continue;
+        };
+        cues.send(PlayCue::at(Cue::SuckPop, position));
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+
+use crate::{component::{DisposalPoint, Player}, events::TankEmptied, resource::{InputValues, Stats}, GameSet, playing};
+
+/// Empties the vacuum tank at disposal points or while the dump key is held and scores the captured ghosts
+pub struct TankPlugin;
+
+impl Plugin for TankPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TankEmptied>()
+            .insert_resource(DumpTimer(Timer::from_seconds(DUMP_TIME, TimerMode::Once)))
+            .add_systems(Update, (
+                dump_tank,
+                use_disposal_points,
+            ).chain().in_set(GameSet::Tank).run_if(playing));
+    }
+}
+
+/// Seconds the dump key has to be held to empty the tank
+const DUMP_TIME: f32 = 1.5;
+/// The player empties the tank by walking this close to a disposal point
+const DISPOSAL_DISTANCE: f32 = 1.5;
+/// Every ghost beyond the first in a single load adds this much to the score multiplier
+const COMBO_STEP: f32 = 0.1;
+
+#[derive(Resource)]
+struct DumpTimer(Timer);
+
+/// Bigger loads are worth more, so it pays off to wait until the tank is full
+fn combo(ghosts: u32) -> f32 {
+    1.0 + COMBO_STEP * ghosts.saturating_sub(1) as f32
+}
+
+fn empty_tank(stats: &mut Stats, tank_emptied_events: &mut EventWriter<TankEmptied>) {
+    if stats.tank == 0 {
+        return;
+    }
+    let ghosts = stats.tank;
+    let multiplier = combo(ghosts);
+    let score = (ghosts as f32 * multiplier).round() as u32;
+    info!("emptied {} ghosts with a combo of {} for {} points", ghosts, multiplier, score);
+    stats.score += score;
+    stats.tank = 0;
+    tank_emptied_events.send(TankEmptied { ghosts, multiplier, score });
+}
+
+fn dump_tank(
+    time: Res<Time>,
+    input_values: Res<InputValues>,
+    mut stats: ResMut<Stats>,
+    mut dump_timer: ResMut<DumpTimer>,
+    mut tank_emptied_events: EventWriter<TankEmptied>,
+) {
+    if !input_values.dumping || stats.tank == 0 {
+        dump_timer.0.reset();
+        return;
+    }
+
+    dump_timer.0.tick(time.delta());
+    if dump_timer.0.just_finished() {
+        empty_tank(&mut stats, &mut tank_emptied_events);
+        dump_timer.0.reset();
+    }
+}
+
+fn use_disposal_points(
+    mut stats: ResMut<Stats>,
+    mut tank_emptied_events: EventWriter<TankEmptied>,
+    players: Query<&Transform, With<Player>>,
+    disposal_points: Query<&GlobalTransform, With<DisposalPoint>>,
+) {
+    let Ok(player) = players.get_single() else {
+        return;
+    };
+    let player = Vec2::new(player.translation.x, player.translation.z);
+    let in_reach = disposal_points.iter().any(|disposal_point| {
+        let position = disposal_point.translation();
+        Vec2::new(position.x, position.z).distance(player) < DISPOSAL_DISTANCE
+    });
+    if in_reach {
+        empty_tank(&mut stats, &mut tank_emptied_events);
+    }
+}
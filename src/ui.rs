@@ -1,26 +1,38 @@
 use bevy::prelude::*;
 
-use crate::{resource::Stats, GameState, enemy_spawner::GhostSpawnConfig};
+use crate::{resource::Stats, settings::OpenSettings, GameState, PauseState, playing, enemy_spawner::GhostSpawnConfig, boss::Boss, events::TankEmptied};
 
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Menu), spawn_main_menu)
+        app.add_systems(OnEnter(GameState::Menu), (cleanup_hud, spawn_main_menu))
             .add_systems(Update, handle_main_menu.run_if(in_state(GameState::Menu)))
             .add_systems(OnExit(GameState::Menu), cleanup_main_menu)
-            .add_systems(OnEnter(GameState::Game), setup)
+            // a restarted game would show the hud twice otherwise
+            .add_systems(OnEnter(GameState::Game), (cleanup_hud, setup))
             .add_systems(Update, (
                 update_stats,
                 update_entities,
-            ).run_if(in_state(GameState::Game)))
+                update_boss_health,
+                update_heat_meter,
+                update_tank,
+                update_slow,
+            ).run_if(playing))
             .add_systems(OnExit(GameState::Game), (update_stats, update_entities))
+            .add_systems(OnEnter(PauseState::Paused), spawn_pause_menu)
+            .add_systems(Update, handle_pause_menu.run_if(in_state(PauseState::Paused)))
+            .add_systems(OnExit(PauseState::Paused), cleanup_pause_menu)
             .add_systems(OnEnter(GameState::GameOver), spawn_restart_button)
             .add_systems(Update, button_system.run_if(in_state(GameState::GameOver)))
             .add_systems(OnExit(GameState::GameOver), cleanup_restart_button);
     }
 }
 
+/// Root of everything shown during a game
+#[derive(Component)]
+struct Hud;
+
 #[derive(Component)]
 struct HealthText;
 
@@ -36,6 +48,30 @@ struct EntityCounter;
 #[derive(Component)]
 struct WaveCounter;
 
+#[derive(Component)]
+struct BossHealthBar;
+
+#[derive(Component)]
+struct BossHealthFill;
+
+#[derive(Component)]
+struct HeatFill;
+
+#[derive(Component)]
+struct TankText;
+
+#[derive(Component)]
+struct SlowText;
+
+fn cleanup_hud(
+    query: Query<Entity, With<Hud>>,
+    mut commands: Commands,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
 fn setup(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
@@ -67,7 +103,8 @@ fn setup(
         }),
     ]),
     )
-    .insert(HealthText);
+    .insert(HealthText)
+    .insert(Hud);
 
     commands.spawn(NodeBundle {
         style: Style {
@@ -79,6 +116,7 @@ fn setup(
         },
         ..default()
     })
+    .insert(Hud)
     .with_children(|parent| {
         parent.spawn(TextBundle::from_section(
             "fartbag",
@@ -105,7 +143,8 @@ fn setup(
             ..default()
         }
     )
-    .insert(FrostOverlay);
+    .insert(FrostOverlay)
+    .insert(Hud);
 
     commands.spawn(
         TextBundle::from("From an &str into a TextBundle with the default font!")
@@ -118,7 +157,8 @@ fn setup(
             },
         ),
     )
-    .insert(EntityCounter);
+    .insert(EntityCounter)
+    .insert(Hud);
 
     commands.spawn(
         TextBundle::from_section(
@@ -137,9 +177,109 @@ fn setup(
             },
         ),
     )
-    .insert(WaveCounter);
+    .insert(WaveCounter)
+    .insert(Hud);
+
+    commands.spawn(NodeBundle {
+        style: Style {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(40.0),
+            left: Val::Percent(25.0),
+            width: Val::Percent(50.0),
+            height: Val::Px(20.0),
+            border: UiRect::all(Val::Px(3.0)),
+            ..default()
+        },
+        border_color: BorderColor(Color::BLACK),
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+        ..default()
+    })
+    .insert(BossHealthBar)
+    .insert(Hud)
+    .with_children(|parent| {
+        parent.spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: Color::PURPLE.into(),
+            ..default()
+        })
+        .insert(BossHealthFill);
+    });
+
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(75.0),
+            left: Val::Px(15.0),
+            width: Val::Px(200.0),
+            height: Val::Px(16.0),
+            border: UiRect::all(Val::Px(3.0)),
+            ..default()
+        },
+        border_color: BorderColor(Color::BLACK),
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+        ..default()
+    })
+    .insert(Hud)
+    .with_children(|parent| {
+        parent.spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(0.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: Color::ORANGE.into(),
+            ..default()
+        })
+        .insert(HeatFill);
+    });
+
+    commands.spawn(
+        TextBundle::from_section(
+            "fartbag",
+            TextStyle {
+                font: asset_server.load("graveyrd.ttf"),
+                font_size: 30.0,
+                ..default()
+            })
+        .with_style(
+            Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(100.0),
+                left: Val::Px(15.0),
+                ..default()
+            },
+        ),
+    )
+    .insert(TankText)
+    .insert(Hud);
+
+    commands.spawn(
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("graveyrd.ttf"),
+                font_size: 30.0,
+                color: Color::CYAN,
+            })
+        .with_style(
+            Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(130.0),
+                left: Val::Px(15.0),
+                ..default()
+            },
+        ),
+    )
+    .insert(SlowText)
+    .insert(Hud);
 }
 
+#[allow(clippy::type_complexity)]
 fn update_stats(
     stats: Res<Stats>,
     ghost_config: Res<GhostSpawnConfig>,
@@ -161,7 +301,74 @@ fn update_stats(
     }
 
     for mut overlay in &mut overlays {
-        overlay.0 = Color::rgba(1.0, 1.0, 1.0, 1.0 - stats.normalized_health()).into();
+        overlay.0 = Color::rgba(1.0, 1.0, 1.0, 1.0 - stats.normalized_health());
+    }
+}
+
+fn update_boss_health(
+    bosses: Query<&Boss>,
+    mut bars: Query<&mut Style, (With<BossHealthBar>, Without<BossHealthFill>)>,
+    mut fills: Query<&mut Style, (With<BossHealthFill>, Without<BossHealthBar>)>,
+) {
+    let boss = bosses.iter().next();
+
+    for mut bar in &mut bars {
+        bar.display = if boss.is_some() { Display::Flex } else { Display::None };
+    }
+
+    if let Some(boss) = boss {
+        for mut fill in &mut fills {
+            fill.width = Val::Percent(boss.normalized_health().max(0.0) * 100.0);
+        }
+    }
+}
+
+const OVERHEAT_BLINK_FREQUENCY: f32 = 4.0;
+
+/// Fills up from orange to red while the vacuum runs and blinks while it is overheated
+fn update_heat_meter(
+    time: Res<Time>,
+    stats: Res<Stats>,
+    mut fills: Query<(&mut Style, &mut BackgroundColor), With<HeatFill>>,
+) {
+    let heat = stats.normalized_heat();
+    for (mut style, mut color) in &mut fills {
+        style.width = Val::Percent(heat * 100.0);
+        *color = if stats.overheated {
+            if ((time.elapsed_seconds() * OVERHEAT_BLINK_FREQUENCY) as u32).is_multiple_of(2) { Color::RED } else { Color::WHITE }
+        } else {
+            Color::rgb(1.0, 0.65 * (1.0 - heat), 0.0)
+        }.into();
+    }
+}
+
+/// Shows the combo of the last emptied tank until the next ghost is captured
+fn update_tank(
+    stats: Res<Stats>,
+    mut tank_emptied_events: EventReader<TankEmptied>,
+    mut last_combo: Local<String>,
+    mut texts: Query<&mut Text, With<TankText>>,
+) {
+    for tank_emptied in tank_emptied_events.read() {
+        *last_combo = format!("  +{} ({} x{:.1})", tank_emptied.score, tank_emptied.ghosts, tank_emptied.multiplier);
+    }
+    if stats.tank > 0 {
+        last_combo.clear();
+    }
+    for mut text in &mut texts {
+        text.sections[0].value = format!("Tank: {}/{}  Score: {}{}", stats.tank, stats.tank_capacity, stats.score, *last_combo);
+        text.sections[0].style.color = if stats.tank_full() { Color::RED } else { Color::WHITE };
+    }
+}
+
+/// Shows how much missing health slows the player down, only while it does
+fn update_slow(
+    stats: Res<Stats>,
+    mut texts: Query<&mut Text, With<SlowText>>,
+) {
+    let slow = (stats.slow() * 100.0).round();
+    for mut text in &mut texts {
+        text.sections[0].value = if slow > 0.0 { format!("Slowed: {}%", slow) } else { String::new() };
     }
 }
 
@@ -175,9 +382,9 @@ fn update_entities(
     }
 }
 
-const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
-const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
-const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+pub const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+pub const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+pub const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
 
 #[derive(Component)]
 struct ButtonNode;
@@ -228,6 +435,7 @@ fn spawn_restart_button(
         });
 }
 
+#[allow(clippy::type_complexity)]
 fn button_system(
     mut game_state: ResMut<NextState<GameState>>,
     mut interaction_query: Query<
@@ -276,6 +484,21 @@ fn cleanup_restart_button(
 #[derive(Component)]
 struct MainMenu;
 
+#[derive(Clone, Copy, Component)]
+enum MainMenuButton {
+    Start,
+    Settings,
+}
+
+impl MainMenuButton {
+    fn label(&self) -> &'static str {
+        match self {
+            MainMenuButton::Start => "Start",
+            MainMenuButton::Settings => "Settings",
+        }
+    }
+}
+
 fn spawn_main_menu(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
@@ -285,72 +508,76 @@ fn spawn_main_menu(
             style: Style {
                 width: Val::Percent(100.0),
                 height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
                 align_items: AlignItems::Center,
                 justify_content: JustifyContent::Center,
+                row_gap: Val::Px(10.0),
                 ..default()
             },
             ..default()
         })
         .insert(MainMenu)
         .with_children(|parent| {
-            parent
-                .spawn(ButtonBundle {
-                    style: Style {
-                        width: Val::Px(150.0),
-                        height: Val::Px(65.0),
-                        border: UiRect::all(Val::Px(5.0)),
-                        // horizontally center child text
-                        justify_content: JustifyContent::Center,
-                        // vertically center child text
-                        align_items: AlignItems::Center,
-                        ..default()
-                    },
-                    border_color: BorderColor(Color::BLACK),
-                    background_color: NORMAL_BUTTON.into(),
-                    ..default()
-                })
-                .with_children(|parent| {
-                    parent.spawn(TextBundle::from_section(
-                        "Start",
-                        TextStyle {
-                            font: asset_server.load("graveyrd.ttf"),
-                            font_size: 40.0,
-                            color: Color::rgb(0.9, 0.9, 0.9),
+            for button in [MainMenuButton::Start, MainMenuButton::Settings] {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            width: Val::Px(200.0),
+                            height: Val::Px(65.0),
+                            border: UiRect::all(Val::Px(5.0)),
+                            // horizontally center child text
+                            justify_content: JustifyContent::Center,
+                            // vertically center child text
+                            align_items: AlignItems::Center,
+                            ..default()
                         },
-                    ));
-                });
+                        border_color: BorderColor(Color::BLACK),
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    })
+                    .insert(button)
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            button.label(),
+                            TextStyle {
+                                font: asset_server.load("graveyrd.ttf"),
+                                font_size: 40.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ));
+                    });
+            }
         });
 }
 
 fn handle_main_menu(
     mut game_state: ResMut<NextState<GameState>>,
+    mut open_settings_events: EventWriter<OpenSettings>,
     mut interaction_query: Query<
         (
             &Interaction,
+            &MainMenuButton,
             &mut BackgroundColor,
             &mut BorderColor,
-            &Children,
         ),
-        (Changed<Interaction>, With<Button>),
+        Changed<Interaction>,
     >,
-    mut text_query: Query<&mut Text>,
 ) {
-    for (interaction, mut color, mut border_color, children) in &mut interaction_query {
-        let mut text = text_query.get_mut(children[0]).unwrap();
+    for (interaction, button, mut color, mut border_color) in &mut interaction_query {
         match *interaction {
             Interaction::Pressed => {
-                text.sections[0].value = "Start".to_string();
                 *color = PRESSED_BUTTON.into();
                 border_color.0 = Color::RED;
-                game_state.set(GameState::Game);
+                match button {
+                    MainMenuButton::Start => game_state.set(GameState::Game),
+                    MainMenuButton::Settings => open_settings_events.send(OpenSettings),
+                }
             }
             Interaction::Hovered => {
-                text.sections[0].value = "Start".to_string();
                 *color = HOVERED_BUTTON.into();
                 border_color.0 = Color::WHITE;
             }
             Interaction::None => {
-                text.sections[0].value = "Start".to_string();
                 *color = NORMAL_BUTTON.into();
                 border_color.0 = Color::BLACK;
             }
@@ -365,4 +592,129 @@ fn cleanup_main_menu(
     for entity in &query {
         commands.entity(entity).despawn_recursive();
     }
-}
\ No newline at end of file
+}
+
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Clone, Copy, Component)]
+enum PauseButton {
+    Resume,
+    Settings,
+    Restart,
+    Quit,
+}
+
+impl PauseButton {
+    fn label(&self) -> &'static str {
+        match self {
+            PauseButton::Resume => "Resume",
+            PauseButton::Settings => "Settings",
+            PauseButton::Restart => "Restart",
+            PauseButton::Quit => "Quit to Menu",
+        }
+    }
+}
+
+fn spawn_pause_menu(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(10.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+            ..default()
+        })
+        .insert(PauseMenu)
+        .with_children(|parent| {
+            for button in [PauseButton::Resume, PauseButton::Settings, PauseButton::Restart, PauseButton::Quit] {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            width: Val::Px(250.0),
+                            height: Val::Px(65.0),
+                            border: UiRect::all(Val::Px(5.0)),
+                            // horizontally center child text
+                            justify_content: JustifyContent::Center,
+                            // vertically center child text
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::BLACK),
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    })
+                    .insert(button)
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            button.label(),
+                            TextStyle {
+                                font: asset_server.load("graveyrd.ttf"),
+                                font_size: 40.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn handle_pause_menu(
+    mut game_state: ResMut<NextState<GameState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    mut open_settings_events: EventWriter<OpenSettings>,
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &PauseButton,
+            &mut BackgroundColor,
+            &mut BorderColor,
+        ),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, button, mut color, mut border_color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PRESSED_BUTTON.into();
+                border_color.0 = Color::RED;
+                match button {
+                    PauseButton::Resume => {}
+                    PauseButton::Settings => {
+                        open_settings_events.send(OpenSettings);
+                        continue;
+                    }
+                    PauseButton::Restart => game_state.set(GameState::Restarting),
+                    PauseButton::Quit => game_state.set(GameState::Menu),
+                }
+                pause_state.set(PauseState::Running);
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+                border_color.0 = Color::WHITE;
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+                border_color.0 = Color::BLACK;
+            }
+        }
+    }
+}
+
+fn cleanup_pause_menu(
+    query: Query<Entity, With<PauseMenu>>,
+    mut commands: Commands,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
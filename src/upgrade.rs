@@ -1,8 +1,10 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
-use rand::{Rng, thread_rng};
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::resource::GlobalEntropy;
+use rand::Rng;
 
-use crate::{events::{WaveEnd, Sucked, PickedUpgrade}, component::{FloatTimer, Suckable}, resource::{CameraSettings, Stats}, GameState};
+use crate::{enemy_spawner::GhostSpawnConfig, events::{WaveEnd, Sucked, PickedUpgrade}, component::{FloatTimer, Pullable, Suckable}, resource::{CameraSettings, Stats, Headless}, vacuum::RangeShape, GameSet, GameState, playing};
 
 pub struct UpgradePlugin;
 
@@ -13,19 +15,33 @@ impl Plugin for UpgradePlugin {
                 spawn_update,
                 detect_suck_events,
                 remove_all_upgrades,
+            ).chain().in_set(GameSet::Upgrades).run_if(playing))
+            .add_systems(Update, (
+                spawn_labels,
                 show_labels,
-            ).run_if(in_state(GameState::Game)))
-            .add_systems(OnEnter(GameState::GameOver), remove_all_upgrades);
+            ).run_if(playing.and_then(not(resource_exists::<Headless>()))))
+            .add_systems(OnEnter(GameState::GameOver), remove_all_upgrades)
+            .add_systems(OnEnter(GameState::Game), kill_all_upgrades);
     }
 }
 
 #[derive(Component)]
-struct Upgrade {
+pub struct Upgrade {
     max_health: f32,
     health: f32,
     regeneration: f32,
     suck_time: f32,
     movement_speed: f32,
+    /// Fraction of the remaining slow from missing health that is taken away
+    slow_reduction: f32,
+    range_length: f32,
+    range_width: f32,
+    range_shape: Option<RangeShape>,
+    heat_capacity: f32,
+    cooldown_speed: f32,
+    tank_capacity: u32,
+    dash_distance: f32,
+    dash_cooldown: f32,
     label: String,
 }
 
@@ -37,6 +53,15 @@ impl Default for Upgrade {
             regeneration: 1.0,
             suck_time: 1.0,
             movement_speed: 1.0,
+            slow_reduction: 0.0,
+            range_length: 1.0,
+            range_width: 1.0,
+            range_shape: None,
+            heat_capacity: 1.0,
+            cooldown_speed: 1.0,
+            tank_capacity: 0,
+            dash_distance: 1.0,
+            dash_cooldown: 1.0,
             label: String::new(),
         }
     }
@@ -53,51 +78,124 @@ impl Upgrade {
         stats.regeneration *= self.regeneration;
         stats.suck_time *= self.suck_time;
         stats.movement_speed *= self.movement_speed;
+        stats.min_speed += (1.0 - stats.min_speed) * self.slow_reduction;
+        stats.range.length *= self.range_length;
+        // the range widens sideways and vertically alike, so floating ghosts stay inside of it
+        stats.range.width *= self.range_width;
+        stats.range.depth *= self.range_width;
+        if let Some(shape) = self.range_shape {
+            stats.range.shape = shape;
+        }
+        stats.heat_capacity *= self.heat_capacity;
+        stats.cooldown_speed *= self.cooldown_speed;
+        stats.tank_capacity += self.tank_capacity;
+        stats.dash_distance *= self.dash_distance;
+        stats.dash_cooldown *= self.dash_cooldown;
     }
 }
 
 impl Upgrade {
+    /// Stronger upgrades offered after defeating a boss
+    fn boss_rewards() -> Vec<Self> {
+        vec![
+            Self {
+                max_health: 1.25,
+                health: 1.0,
+                label: String::from("Increases maximum health by 25% and heals you fully"),
+                ..default()
+            },
+            Self {
+                suck_time: 0.75,
+                label: String::from("Decreases time to vacuum ghosts by 25%"),
+                ..default()
+            },
+            Self {
+                regeneration: 1.2,
+                movement_speed: 1.2,
+                label: String::from("Increases regeneration and movement speed by 20%"),
+                ..default()
+            },
+            Self {
+                range_width: 1.25,
+                range_shape: Some(RangeShape::Prism),
+                label: String::from("Reshapes the vacuum into a prism and widens it by 25%"),
+                ..default()
+            },
+            Self {
+                range_length: 1.25,
+                range_shape: Some(RangeShape::Wedge),
+                label: String::from("Reshapes the vacuum into a wedge and lengthens it by 25%"),
+                ..default()
+            },
+        ]
+    }
+
     fn all() -> Vec<Self> {
         vec![
             Self {
                 max_health: 1.1,
-                health: 0.0,
-                regeneration: 1.0,
-                suck_time: 1.0,
-                movement_speed: 1.0,
                 label: String::from("Increases maximum health by 10%"),
+                ..default()
             },
             Self {
-                max_health: 1.0,
                 health: 0.2,
-                regeneration: 1.0,
-                suck_time: 1.0,
-                movement_speed: 1.0,
                 label: String::from("Heals 20% of your maximum health"),
+                ..default()
             },
             Self {
-                max_health: 1.0,
-                health: 0.0,
                 regeneration: 1.1,
-                suck_time: 1.0,
-                movement_speed: 1.0,
                 label: String::from("Increases health regeneration by 10%"),
+                ..default()
             },
             Self {
-                max_health: 1.0,
-                health: 0.0,
-                regeneration: 1.0,
                 suck_time: 0.9,
-                movement_speed: 1.0,
                 label: String::from("Decreases time to vacuum ghosts by 10%"),
+                ..default()
             },
             Self {
-                max_health: 1.0,
-                health: 0.0,
-                regeneration: 1.0,
-                suck_time: 1.0,
                 movement_speed: 1.1,
                 label: String::from("Increases movement speed by 10%"),
+                ..default()
+            },
+            Self {
+                slow_reduction: 0.3,
+                label: String::from("Damage slows you down 30% less"),
+                ..default()
+            },
+            Self {
+                range_length: 1.15,
+                label: String::from("Increases the reach of the vacuum by 15%"),
+                ..default()
+            },
+            Self {
+                range_width: 1.15,
+                label: String::from("Increases the width of the vacuum by 15%"),
+                ..default()
+            },
+            Self {
+                heat_capacity: 1.2,
+                label: String::from("The vacuum runs 20% longer before it overheats"),
+                ..default()
+            },
+            Self {
+                cooldown_speed: 1.25,
+                label: String::from("The vacuum cools down 25% faster"),
+                ..default()
+            },
+            Self {
+                tank_capacity: 3,
+                label: String::from("The tank holds 3 more ghosts"),
+                ..default()
+            },
+            Self {
+                dash_distance: 1.2,
+                label: String::from("Increases dash distance by 20%"),
+                ..default()
+            },
+            Self {
+                dash_cooldown: 0.85,
+                label: String::from("Decreases dash cooldown by 15%"),
+                ..default()
             },
         ]
     }
@@ -106,24 +204,26 @@ impl Upgrade {
 #[derive(Component)]
 struct UpgradeLabel(Entity);
 
+const DIRTBAG_MASS: f32 = 2.0;
+
 fn spawn_update(
     asset_server: Res<AssetServer>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    ghost_spawn_config: Res<GhostSpawnConfig>,
     mut wave_end_event: EventReader<WaveEnd>,
     mut commands: Commands,
 ) {
     for _ in wave_end_event.read() {
         info!("spawning upgrades");
-        let mut upgrades = Upgrade::all();
-
-        let label_text_style = TextStyle {
-            font: asset_server.load("graveyrd.ttf"),
-            font_size: 25.0,
-            color: Color::ORANGE,
+        let mut upgrades = if ghost_spawn_config.is_boss_wave() {
+            Upgrade::boss_rewards()
+        }
+        else {
+            Upgrade::all()
         };
 
-        let random_index = thread_rng().gen_range(0..upgrades.len());
+        let random_index = rng.gen_range(0..upgrades.len());
         let upgrade_left = upgrades.remove(random_index);
-        let label_left = upgrade_left.label.clone();
         let entity_left = commands.spawn(SceneBundle {
             scene: asset_server.load("dirtbag.glb#Scene0"),
             transform: Transform::from_xyz(-5.0, 0.0, 0.0),
@@ -132,41 +232,18 @@ fn spawn_update(
         .insert(upgrade_left)
         .insert(FloatTimer::new((0.0, 0.5)))
         .insert(Collider::ball(0.5))
+        .insert(RigidBody::KinematicPositionBased)
         .insert(Sensor)
         .insert(CollisionGroups::new(Group::GROUP_5, Group::GROUP_3))
         .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(Pullable(DIRTBAG_MASS))
         .insert(Suckable)
         .id();
 
         info!("spawned {:?}", entity_left);
 
-        commands
-        .spawn((
-            NodeBundle {
-                style: Style {
-                    position_type: PositionType::Absolute,
-                    justify_content: JustifyContent::Center,
-                    ..default()
-                },
-                ..default()
-            },
-            UpgradeLabel(entity_left),
-        ))
-        .with_children(|parent| {
-            parent.spawn(
-                TextBundle::from_section(label_left, label_text_style.clone())
-                    .with_style(Style {
-                        position_type: PositionType::Absolute,
-                        bottom: Val::ZERO,
-                        ..default()
-                    })
-                    .with_no_wrap(),
-            );
-        });
-
-        let random_index = thread_rng().gen_range(0..upgrades.len());
+        let random_index = rng.gen_range(0..upgrades.len());
         let upgrade_right = upgrades.remove(random_index);
-        let label_right = upgrade_right.label.clone();
         let entity_right = commands.spawn(SceneBundle {
             scene: asset_server.load("dirtbag.glb#Scene0"),
             transform: Transform::from_xyz(5.0, 0.0, 0.0),
@@ -175,14 +252,30 @@ fn spawn_update(
         .insert(upgrade_right)
         .insert(FloatTimer::new((0.0, 0.5)))
         .insert(Collider::ball(0.5))
+        .insert(RigidBody::KinematicPositionBased)
         .insert(Sensor)
         .insert(CollisionGroups::new(Group::GROUP_5, Group::GROUP_3))
         .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(Pullable(DIRTBAG_MASS))
         .insert(Suckable)
         .id();
 
-        info!("spawned {:?}", entity_left);
+        info!("spawned {:?}", entity_right);
+    }
+}
+
+fn spawn_labels(
+    asset_server: Res<AssetServer>,
+    upgrades: Query<(Entity, &Upgrade), Added<Upgrade>>,
+    mut commands: Commands,
+) {
+    let label_text_style = TextStyle {
+        font: asset_server.load("graveyrd.ttf"),
+        font_size: 25.0,
+        color: Color::ORANGE,
+    };
 
+    for (entity, upgrade) in &upgrades {
         commands
         .spawn((
             NodeBundle {
@@ -193,11 +286,11 @@ fn spawn_update(
                 },
                 ..default()
             },
-            UpgradeLabel(entity_right),
+            UpgradeLabel(entity),
         ))
         .with_children(|parent| {
             parent.spawn(
-                TextBundle::from_section(label_right, label_text_style.clone())
+                TextBundle::from_section(upgrade.label.clone(), label_text_style.clone())
                     .with_style(Style {
                         position_type: PositionType::Absolute,
                         bottom: Val::ZERO,
@@ -252,6 +345,17 @@ fn remove_all_upgrades(
     }
 }
 
+/// Dirtbags left over from the last game are removed before a new one starts
+#[allow(clippy::type_complexity)]
+fn kill_all_upgrades(
+    dirtbags: Query<Entity, Or<(With<Upgrade>, With<UpgradeLabel>)>>,
+    mut commands: Commands,
+) {
+    for entity in &dirtbags {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
 fn show_labels (
     mut camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
     mut labels: Query<(&mut Style, &UpgradeLabel)>,
@@ -1,64 +1,308 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::EntityHashMap};
+use bevy_rapier3d::prelude::*;
 
-use crate::{collision_events::SuckEvent, component::*, resource::*, common::Random, events::Sucked, GameState};
+use crate::{archetype::Archetype, boss::Boss, collision_events::SuckEvent, component::*, resource::*, events::{HeatEvent, Sucked}, GameSet, playing};
 
 pub struct VacuumPlugin;
 
 impl Plugin for VacuumPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<Sucked>()
-            .add_systems(Update, (detect_suckage, update_suckage).run_if(in_state(GameState::Game)));
+            .add_event::<HeatEvent>()
+            .add_systems(Update, (detect_suckage, update_suckage, pull, resize_range, update_heat).chain().in_set(GameSet::Vacuum).run_if(playing));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RangeShape {
+    /// Narrows to a point at the nozzle and ends in an ellipse
+    Cone,
+    /// Spans the whole depth and widens from a third of its width at the nozzle
+    Prism,
+    /// Spans the whole depth and widens from a line at the nozzle
+    Wedge,
+}
+
+/// The area in front of the nozzle that vacuums ghosts and upgrades.
+/// The nozzle points along its local -Y axis, so the length lies on Y, the width on X and the depth on Z.
+#[derive(Clone, Component, Copy, Debug, PartialEq)]
+pub struct VacuumRange {
+    pub shape: RangeShape,
+    pub length: f32,
+    pub width: f32,
+    pub depth: f32,
+}
+
+const CONE_SEGMENTS: usize = 16;
+
+impl Default for VacuumRange {
+    fn default() -> Self {
+        Self {
+            shape: RangeShape::Cone,
+            length: 2.0,
+            width: 2.0,
+            depth: 2.0,
+        }
+    }
+}
+
+impl VacuumRange {
+    /// Corners of the range, its back end lies at the origin, so a longer range only reaches further forward
+    fn points(&self) -> Vec<Vect> {
+        let (back, front) = (0.0, -self.length);
+        let (x, z) = (self.width / 2.0, self.depth / 2.0);
+        match self.shape {
+            RangeShape::Cone => {
+                let mut points = vec![Vect::new(0.0, back, 0.0)];
+                points.extend((0..CONE_SEGMENTS).map(|i| {
+                    let angle = i as f32 * std::f32::consts::TAU / CONE_SEGMENTS as f32;
+                    Vect::new(angle.cos() * x, front, angle.sin() * z)
+                }));
+                points
+            }
+            RangeShape::Prism => vec![
+                Vect::new(x / 3.0, back, z),
+                Vect::new(x / 3.0, back, -z),
+                Vect::new(-x / 3.0, back, z),
+                Vect::new(-x / 3.0, back, -z),
+                Vect::new(x, front, z),
+                Vect::new(x, front, -z),
+                Vect::new(-x, front, z),
+                Vect::new(-x, front, -z),
+            ],
+            RangeShape::Wedge => vec![
+                Vect::new(0.0, back, z),
+                Vect::new(0.0, back, -z),
+                Vect::new(x, front, z),
+                Vect::new(x, front, -z),
+                Vect::new(-x, front, z),
+                Vect::new(-x, front, -z),
+            ],
+        }
+    }
+
+    pub fn collider(&self) -> Collider {
+        Collider::convex_hull(&self.points()).expect("vacuum range has no volume")
+    }
+
+    /// The middle of the far end of the range in local space
+    pub fn front(&self) -> Vec3 {
+        Vec3::NEG_Y * self.length
+    }
+}
+
+/// Rebuilds the nozzle collider whenever an upgrade has changed the range
+fn resize_range(
+    stats: Res<Stats>,
+    mut nozzles: Query<(&mut VacuumRange, Entity), With<Nozzle>>,
+    mut commands: Commands,
+) {
+    for (mut range, entity) in &mut nozzles {
+        if *range != stats.range {
+            info!("resizing vacuum range to {:?}", stats.range);
+            *range = stats.range;
+            commands.entity(entity).insert(range.collider());
+        }
     }
 }
 
 fn detect_suckage(
-    config: Res<Stats>,
     mut suck_events: EventReader<SuckEvent>,
-    mut query: Query<&mut Transform, With<Suckable>>,
+    mut query: Query<Option<&mut SuckProgress>, (With<Suckable>, Without<Boss>)>,
     mut commands: Commands,
 ) {
+    // only the last event of an entity counts, it may have left the range again in the same frame,
+    // entities are hashed without a random seed, so they are handled in the same order in every run
+    let mut in_range = EntityHashMap::default();
     for suck_event in suck_events.read() {
         match suck_event {
             SuckEvent::Start(entity) => {
                 info!("Started vacuuming {:?}", entity);
-                if let Some(mut cmds) = commands.get_entity(*entity) {
-                    cmds.try_insert(SuckTimer(Timer::from_seconds(config.suck_time, TimerMode::Once)));
-                }
+                in_range.insert(*entity, true);
             }
             SuckEvent::Stop(entity) => {
                 info!("Stopped vacuuming {:?}", entity);
-                if let Some(mut cmds) = commands.get_entity(*entity) {
-                    cmds.remove::<SuckTimer>();
-                    if let Ok(mut suckable) = query.get_mut(*entity) {
-                        suckable.scale = Vec3::ONE;
-                    }
+                in_range.insert(*entity, false);
+            }
+        }
+    }
+
+    for (entity, in_range) in in_range {
+        // bosses are not vacuumed at once but drain their health instead
+        let Ok(progress) = query.get_mut(entity) else {
+            continue;
+        };
+        match progress {
+            Some(mut progress) => progress.in_range = in_range,
+            None if in_range => {
+                if let Some(mut cmds) = commands.get_entity(entity) {
+                    cmds.try_insert(SuckProgress { progress: 0.0, in_range, elapsed: 0.0 });
                 }
             }
+            None => {}
         }
     }
 }
 
-const SUCKING_SPEED: f32 = 2.0;
+/// Progress lost per second outside of the vacuum range
+const DECAY_RATE: f32 = 0.5;
+const WOBBLE_ANGLE: f32 = 0.4;
+const WOBBLE_FREQUENCY: f32 = 8.0;
 
+/// Advances the progress of everything inside the vacuum range and lets it decay outside of it.
+/// Entities shrink with their progress and wobble harder the more resistance they have left.
+/// Ghosts that don't fit into the tank anymore are held right before they are vacuumed up.
+#[allow(clippy::type_complexity)]
 fn update_suckage(
     time: Res<Time>,
+    stats: Res<Stats>,
     mut suck_events: EventWriter<Sucked>,
-    mut query: Query<(&mut SuckTimer, &mut Transform, Entity), Without<Nozzle>>,
+    mut query: Query<(&mut SuckProgress, &mut Transform, Option<&SuckResistance>, Option<&Archetype>, Has<Ghost>, Entity), Without<Nozzle>>,
     nozzles: Query<&GlobalTransform, With<Nozzle>>,
+    mut commands: Commands,
 ) {
     let Ok(nozzle) = nozzles.get_single() else {
         return;
     };
-    for (mut timer, mut transform, entity) in &mut query {
-        timer.tick(time.delta());
-        transform.scale = Vec3::ONE * timer.percent_left();
-        transform.rotation = Quat::random();
-        let diff = nozzle.translation() - transform.translation;
-        let direction = diff.normalize_or_zero() * time.delta_seconds() * SUCKING_SPEED;
-        transform.translation += direction;
-        if timer.just_finished() {
-            info!("Sending sucked event for {:?}", entity);
-            suck_events.send(Sucked(entity));
+    let mut room = stats.tank_capacity.saturating_sub(stats.tank);
+    for (mut progress, mut transform, resistance, archetype, ghost, entity) in &mut query {
+        let resistance = resistance.map_or(1.0, |r| r.0);
+        let scale = archetype.map_or(1.0, |a| a.stats().scale);
+
+        if progress.in_range {
+            let before = progress.progress;
+            progress.progress = (progress.progress + time.delta_seconds() / (stats.suck_time * resistance)).min(1.0);
+            if before < 1.0 && progress.progress >= 1.0 {
+                if ghost && room == 0 {
+                    progress.progress = before;
+                }
+                else {
+                    info!("Sending sucked event for {:?}", entity);
+                    suck_events.send(Sucked(entity));
+                    if ghost {
+                        room -= 1;
+                    }
+                }
+            }
+        }
+        else {
+            progress.progress -= time.delta_seconds() * DECAY_RATE;
+            if progress.progress <= 0.0 {
+                commands.entity(entity).remove::<SuckProgress>();
+                transform.scale = Vec3::ONE * scale;
+                continue;
+            }
+        }
+
+        transform.scale = Vec3::ONE * scale * (1.0 - progress.progress);
+        progress.elapsed += time.delta_seconds();
+
+        let remaining = (1.0 - progress.progress) * resistance;
+        let phase = progress.elapsed * WOBBLE_FREQUENCY * (1.0 + progress.progress);
+        let wobble = Quat::from_rotation_z(phase.sin() * WOBBLE_ANGLE * remaining)
+            * Quat::from_rotation_x(phase.cos() * WOBBLE_ANGLE * remaining * 0.5);
+        let vantage = Vec3::new(nozzle.translation().x, transform.translation.y, nozzle.translation().z);
+        if vantage != transform.translation {
+            transform.look_at(vantage, Vec3::Y);
+        }
+        transform.rotation *= wobble;
+    }
+}
+
+const PULL_FORCE: f32 = 25.0;
+/// Entities are pulled from this many range lengths away
+const PULL_REACH: f32 = 1.5;
+/// Pullables that aren't ghosts are tugged with this fraction of the force before they are inside of the range
+const TUG_FACTOR: f32 = 0.3;
+const PULL_DAMPING: f32 = 3.0;
+
+/// The pull is strongest close to the nozzle and along its axis
+fn pull_strength(nozzle: &GlobalTransform, range: &VacuumRange, position: Vec3) -> f32 {
+    let offset = position - nozzle.translation();
+    let falloff = 1.0 - offset.length() / (range.length * PULL_REACH);
+    // we have to use down here as forward because the nozzle is rotated by 90°
+    let alignment = offset.normalize_or_zero().dot(nozzle.down()).max(0.0);
+    PULL_FORCE * falloff.max(0.0) * (0.25 + 0.75 * alignment)
+}
+
+/// Makes everything inside of the vacuum range a dynamic body and pulls it towards the nozzle.
+/// Other objects close to the nozzle are tugged as well, ghosts outside of the range keep moving on their own.
+/// Pullables are kinematic while they aren't pulled, so their colliders are already attached to a body.
+#[allow(clippy::type_complexity)]
+fn pull(
+    stats: Res<Stats>,
+    input_values: Res<InputValues>,
+    nozzles: Query<(&GlobalTransform, &VacuumRange), With<Nozzle>>,
+    mut pullables: Query<(&Pullable, &Transform, &mut RigidBody, Option<&SuckProgress>, Option<&mut ExternalForce>, Has<Ghost>, Entity)>,
+    mut commands: Commands,
+) {
+    let nozzle = nozzles.get_single().ok().filter(|_| input_values.mouse_pressed && stats.can_vacuum());
+
+    for (pullable, transform, mut body, progress, force, ghost, entity) in &mut pullables {
+        let in_range = progress.is_some_and(|p| p.in_range);
+        let strength = match nozzle {
+            Some((nozzle, range)) if in_range || !ghost => {
+                let strength = pull_strength(nozzle, range, transform.translation);
+                if in_range { strength } else { strength * TUG_FACTOR }
+            }
+            _ => 0.0,
+        };
+
+        if strength <= 0.0 {
+            if force.is_some() {
+                *body = RigidBody::KinematicPositionBased;
+                commands.entity(entity)
+                    .remove::<(Velocity, ExternalForce, GravityScale, Damping, LockedAxes, AdditionalMassProperties)>();
+            }
+            continue;
+        }
+
+        let nozzle_translation = nozzle.map_or(Vec3::ZERO, |(nozzle, _)| nozzle.translation());
+        let mut direction = (nozzle_translation - transform.translation).normalize_or_zero();
+        // things that are only tugged keep their height
+        if !in_range {
+            direction.y = 0.0;
+        }
+
+        match force {
+            Some(mut force) => force.force = direction * strength,
+            None => {
+                *body = RigidBody::Dynamic;
+                // the ghost may have been vacuumed up this frame
+                commands.entity(entity)
+                    .try_insert(Velocity::zero())
+                    .try_insert(ExternalForce { force: direction * strength, ..default() })
+                    .try_insert(GravityScale(0.0))
+                    .try_insert(Damping { linear_damping: PULL_DAMPING, angular_damping: 0.0 })
+                    .try_insert(LockedAxes::ROTATION_LOCKED)
+                    .try_insert(AdditionalMassProperties::Mass(pullable.0));
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Heats the vacuum up while it runs and shuts it off until it has cooled down completely once it overheats
+fn update_heat(
+    time: Res<Time>,
+    input_values: Res<InputValues>,
+    mut stats: ResMut<Stats>,
+    mut heat_events: EventWriter<HeatEvent>,
+) {
+    if input_values.mouse_pressed && stats.can_vacuum() {
+        stats.heat += time.delta_seconds();
+    }
+    else {
+        stats.heat = (stats.heat - time.delta_seconds() * stats.cooldown_speed).max(0.0);
+    }
+
+    if !stats.overheated && stats.heat >= stats.heat_capacity {
+        info!("vacuum overheated");
+        stats.overheated = true;
+        heat_events.send(HeatEvent::Overheated);
+    }
+    else if stats.overheated && stats.heat <= 0.0 {
+        info!("vacuum cooled down");
+        stats.overheated = false;
+        heat_events.send(HeatEvent::Cooled);
+    }
+}
@@ -0,0 +1,312 @@
+use bevy::{prelude::*, asset::{AssetLoader, AsyncReadExt, LoadContext, io::Reader}, utils::BoxedFuture};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::archetype::Archetype;
+
+/// Loads the wave definitions from `*.waves.ron` files
+pub struct WavesPlugin;
+
+impl Plugin for WavesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<WaveDefinitions>()
+            .register_asset_loader(WaveDefinitionsLoader)
+            .add_systems(Startup, load_waves);
+    }
+}
+
+pub const WAVES_PATH: &str = "default.waves.ron";
+
+#[derive(Resource)]
+pub struct Waves(pub Handle<WaveDefinitions>);
+
+fn load_waves(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    commands.insert_resource(Waves(asset_server.load(WAVES_PATH)));
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum SpawnPattern {
+    /// Spawns single ghosts at a random angle around the player
+    Random { radius: f32 },
+    /// Spawns `size` ghosts next to each other at a random angle around the player
+    Burst { radius: f32, size: u32 },
+    /// Spawns `size` ghosts evenly spread around the player
+    Surround { radius: f32, size: u32 },
+    /// Lets `size` ghosts rise out of graves that are off-screen or far enough away from the player
+    Graves { size: u32 },
+}
+
+/// Used by [`SpawnPattern::Graves`] if there are no graves to rise from
+const FALLBACK_RADIUS: f32 = 10.0;
+
+impl SpawnPattern {
+    /// Returns the offsets from the player of all ghosts spawned at once, given a random angle
+    pub fn offsets(&self, angle: f32) -> Vec<Vec3> {
+        let offset = |angle: f32, radius: f32| Vec3::new(angle.sin() * radius, 0.0, angle.cos() * radius);
+        match *self {
+            SpawnPattern::Random { radius } => vec![offset(angle, radius)],
+            SpawnPattern::Burst { radius, size } => (0..size)
+                .map(|i| offset(angle + i as f32 / radius, radius))
+                .collect(),
+            SpawnPattern::Surround { radius, size } => (0..size)
+                .map(|i| offset(angle + i as f32 * std::f32::consts::TAU / size as f32, radius))
+                .collect(),
+            SpawnPattern::Graves { size } => (0..size)
+                .map(|i| offset(angle + i as f32, FALLBACK_RADIUS))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WaveDefinition {
+    pub ghost_count: u32,
+    pub spawn_interval: f32,
+    pub damage: f32,
+    pub speed: f32,
+    /// Weights of the archetypes spawned during this wave
+    pub enemy_mix: Vec<(Archetype, u32)>,
+    pub spawn_pattern: SpawnPattern,
+}
+
+impl Default for WaveDefinition {
+    fn default() -> Self {
+        Self {
+            ghost_count: 25,
+            spawn_interval: 0.8,
+            damage: 8.0,
+            speed: 2.0,
+            enemy_mix: vec![(Archetype::Common, 1)],
+            spawn_pattern: SpawnPattern::Random { radius: 10.0 },
+        }
+    }
+}
+
+impl WaveDefinition {
+    pub fn pick_archetype<R: Rng + ?Sized>(&self, rng: &mut R) -> Archetype {
+        let total: u32 = self.enemy_mix.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return Archetype::Common;
+        }
+        let mut pick = rng.gen_range(0..total);
+        for (archetype, weight) in &self.enemy_mix {
+            if pick < *weight {
+                return *archetype;
+            }
+            pick -= weight;
+        }
+        Archetype::Common
+    }
+}
+
+/// Scales the last authored wave for every wave after it, up to the given limits
+#[derive(Clone, Debug, Deserialize)]
+pub struct EndlessRule {
+    pub ghost_count_factor: f32,
+    pub spawn_interval_factor: f32,
+    pub damage_factor: f32,
+    pub speed_factor: f32,
+    pub max_ghost_count: u32,
+    pub min_spawn_interval: f32,
+    pub max_damage: f32,
+    pub max_speed: f32,
+}
+
+impl EndlessRule {
+    fn apply(&self, wave: &WaveDefinition, times: i32) -> WaveDefinition {
+        WaveDefinition {
+            ghost_count: ((wave.ghost_count as f32 * self.ghost_count_factor.powi(times)) as u32).clamp(1, self.max_ghost_count),
+            spawn_interval: (wave.spawn_interval * self.spawn_interval_factor.powi(times)).max(self.min_spawn_interval),
+            damage: (wave.damage * self.damage_factor.powi(times)).min(self.max_damage),
+            speed: (wave.speed * self.speed_factor.powi(times)).min(self.max_speed),
+            ..wave.clone()
+        }
+    }
+}
+
+/// A boss replaces the regular ghosts of every `every`th wave
+#[derive(Clone, Debug, Deserialize)]
+pub struct BossDefinition {
+    pub every: u32,
+    pub health: f32,
+    pub speed: f32,
+    pub damage: f32,
+    pub scale: f32,
+    /// Seconds between two attacks
+    pub attack_interval: f32,
+    /// Number of ghosts summoned at once
+    pub minions: u32,
+    pub projectile_speed: f32,
+}
+
+#[derive(Asset, Debug, Deserialize, TypePath)]
+pub struct WaveDefinitions {
+    pub waves: Vec<WaveDefinition>,
+    pub endless: EndlessRule,
+    #[serde(default)]
+    pub boss: Option<BossDefinition>,
+}
+
+impl WaveDefinitions {
+    /// Returns the definition of the given wave, starting at 1
+    pub fn wave(&self, number: u32) -> WaveDefinition {
+        let Some(last) = self.waves.last() else {
+            return WaveDefinition::default();
+        };
+        let index = number.max(1) as usize - 1;
+        match self.waves.get(index) {
+            Some(wave) => wave.clone(),
+            None => self.endless.apply(last, (index + 1 - self.waves.len()) as i32),
+        }
+    }
+
+    /// A wave without ghosts could never be finished by eliminating them
+    fn validate(&self) -> Result<(), String> {
+        if let Some(index) = self.waves.iter().position(|wave| wave.ghost_count == 0) {
+            return Err(format!("wave {} has no ghosts", index + 1));
+        }
+        if self.endless.max_ghost_count == 0 {
+            return Err("endless waves have no ghosts".to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns the boss of the given wave if it is a boss wave
+    pub fn boss(&self, number: u32) -> Option<BossDefinition> {
+        self.boss
+            .as_ref()
+            .filter(|boss| boss.every > 0 && number.is_multiple_of(boss.every))
+            .cloned()
+    }
+}
+
+#[derive(Default)]
+struct WaveDefinitionsLoader;
+
+impl AssetLoader for WaveDefinitionsLoader {
+    type Asset = WaveDefinitions;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let definitions: WaveDefinitions = ron::de::from_bytes(&bytes)?;
+            definitions.validate()?;
+            Ok(definitions)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definitions() -> WaveDefinitions {
+        WaveDefinitions {
+            waves: vec![
+                WaveDefinition { ghost_count: 10, ..default() },
+                WaveDefinition { ghost_count: 20, spawn_interval: 1.0, damage: 10.0, speed: 2.0, ..default() },
+            ],
+            endless: EndlessRule {
+                ghost_count_factor: 1.5,
+                spawn_interval_factor: 0.5,
+                damage_factor: 2.0,
+                speed_factor: 1.25,
+                max_ghost_count: 100,
+                min_spawn_interval: 0.1,
+                max_damage: 100.0,
+                max_speed: 5.0,
+            },
+            boss: Some(BossDefinition {
+                every: 3,
+                health: 20.0,
+                speed: 1.5,
+                damage: 25.0,
+                scale: 3.0,
+                attack_interval: 4.0,
+                minions: 3,
+                projectile_speed: 6.0,
+            }),
+        }
+    }
+
+    #[test]
+    fn authored_waves_are_used_as_they_are() {
+        let definitions = definitions();
+        assert_eq!(definitions.wave(1).ghost_count, 10);
+        assert_eq!(definitions.wave(2).ghost_count, 20);
+        // there is no wave 0, it is treated like the first one
+        assert_eq!(definitions.wave(0).ghost_count, 10);
+    }
+
+    #[test]
+    fn endless_waves_scale_the_last_wave() {
+        let definitions = definitions();
+        let third = definitions.wave(3);
+        assert_eq!(third.ghost_count, 30);
+        assert_eq!(third.spawn_interval, 0.5);
+        assert_eq!(third.damage, 20.0);
+        assert_eq!(third.speed, 2.5);
+
+        let fourth = definitions.wave(4);
+        assert_eq!(fourth.ghost_count, 45);
+        assert_eq!(fourth.spawn_interval, 0.25);
+        assert_eq!(fourth.damage, 40.0);
+        assert_eq!(fourth.speed, 3.125);
+    }
+
+    #[test]
+    fn far_endless_waves_are_clamped() {
+        let far = definitions().wave(50);
+        assert_eq!(far.ghost_count, 100);
+        assert_eq!(far.spawn_interval, 0.1);
+        assert_eq!(far.damage, 100.0);
+        assert_eq!(far.speed, 5.0);
+    }
+
+    #[test]
+    fn waves_without_ghosts_are_rejected() {
+        let mut definitions = definitions();
+        assert!(definitions.validate().is_ok());
+        definitions.waves[1].ghost_count = 0;
+        assert_eq!(definitions.validate(), Err("wave 2 has no ghosts".to_string()));
+    }
+
+    #[test]
+    fn without_waves_the_default_is_used() {
+        let definitions = WaveDefinitions { waves: Vec::new(), ..definitions() };
+        assert_eq!(definitions.wave(7).ghost_count, WaveDefinition::default().ghost_count);
+    }
+
+    #[test]
+    fn bosses_come_every_few_waves() {
+        let definitions = definitions();
+        let bosses: Vec<u32> = (1..=9).filter(|wave| definitions.boss(*wave).is_some()).collect();
+        assert_eq!(bosses, vec![3, 6, 9]);
+
+        let no_bosses = WaveDefinitions { boss: None, ..definitions };
+        assert!(no_bosses.boss(3).is_none());
+    }
+
+    #[test]
+    fn default_waves_parse() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join(WAVES_PATH);
+        let definitions: WaveDefinitions = ron::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert!(!definitions.waves.is_empty());
+        assert!(definitions.validate().is_ok());
+    }
+}